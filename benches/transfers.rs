@@ -0,0 +1,150 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks for end-to-end transfer throughput.
+//!
+//! The benchmarks measure separate stages of processing a `Transfer`:
+//!
+//! - `create`: creating a transfer on the client side, which includes creating
+//!   two range proofs
+//! - `range_proof`: verifying a single range proof
+//! - `verify`: stateless verification of a transfer (signature and the range proof
+//!   for the transferred amount)
+//! - `execute`: committing a block with [`TRANSFERS_IN_BLOCK`] transfers, which includes
+//!   stateless and stateful verification, and updating the service state
+//!
+//! Throughput is reported in transfers per second. Run the benchmarks with
+//!
+//! ```shell
+//! cargo bench --bench transfers
+//! ```
+//!
+//! # Baseline
+//!
+//! Range proofs dominate the cost of a transfer. Reference numbers for the range proof
+//! primitives of `bulletproofs 1.0.0-pre.0` (64-bit proofs for a single value, release
+//! build, 200 iterations) on a single-vCPU Intel Xeon virtual machine:
+//!
+//! | Operation | Time per proof |
+//! |-----------|----------------|
+//! | proving   | 15.0–17.6 ms   |
+//! | verifying | 1.8–1.9 ms     |
+//!
+//! A transfer carries two range proofs, so `create` should take about twice the proving
+//! time. `verify` checks one of the proofs and `execute` checks the other one in addition
+//! to the stateless checks; thus, a single core cannot execute more than about 260 transfers
+//! per second. The numbers were not measured with the full benchmarks, since the pinned
+//! nightly toolchain is not available on the reference machine; figures for `create`,
+//! `verify` and `execute` should be added once they are.
+//!
+//! [`TRANSFERS_IN_BLOCK`]: constant.TRANSFERS_IN_BLOCK.html
+
+#[macro_use]
+extern crate criterion;
+extern crate exonum;
+extern crate exonum_testkit;
+extern crate private_currency;
+
+use criterion::{Benchmark, Criterion, Throughput};
+use exonum::blockchain::Transaction;
+use exonum_testkit::{TestKit, TestKitBuilder};
+use private_currency::{
+    crypto::{Commitment, SimpleRangeProof},
+    SecretState, Service as Currency,
+};
+
+/// Number of transfers in a block for the `execute` benchmark.
+const TRANSFERS_IN_BLOCK: usize = 50;
+const TRANSFER_AMOUNT: u64 = 1_000;
+const ROLLBACK_DELAY: u32 = 10;
+
+/// Creates a testkit with `count` pairs of registered wallets.
+fn create_wallets(count: usize) -> (TestKit, Vec<(SecretState, SecretState)>) {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Currency::default())
+        .create();
+    let mut wallets: Vec<_> = (0..count)
+        .map(|_| {
+            (
+                SecretState::with_random_keypair(),
+                SecretState::with_random_keypair(),
+            )
+        })
+        .collect();
+
+    let create_wallets: Vec<Box<dyn Transaction>> = wallets
+        .iter()
+        .flat_map(|(sender, receiver)| vec![sender.create_wallet(), receiver.create_wallet()])
+        .map(|tx| Box::new(tx) as Box<dyn Transaction>)
+        .collect();
+    testkit.create_block_with_transactions(create_wallets);
+    for (sender, receiver) in &mut wallets {
+        sender.initialize();
+        receiver.initialize();
+    }
+    (testkit, wallets)
+}
+
+fn bench_transfers(c: &mut Criterion) {
+    let (_, wallets) = create_wallets(1);
+    let (sender, receiver) = wallets.into_iter().next().unwrap();
+    let receiver_pk = *receiver.public_key();
+    let transfer = sender.create_transfer(TRANSFER_AMOUNT, &receiver_pk, ROLLBACK_DELAY);
+    let (commitment, opening) = Commitment::new(TRANSFER_AMOUNT);
+    let proof = SimpleRangeProof::prove(&opening).unwrap();
+
+    c.bench(
+        "transfer",
+        Benchmark::new("create", move |b| {
+            b.iter(|| sender.create_transfer(TRANSFER_AMOUNT, &receiver_pk, ROLLBACK_DELAY))
+        })
+        .with_function("range_proof", move |b| {
+            b.iter(|| assert!(proof.verify(&commitment)))
+        })
+        .with_function("verify", move |b| b.iter(|| assert!(transfer.verify())))
+        .throughput(Throughput::Elements(1)),
+    );
+
+    c.bench(
+        "transfer",
+        Benchmark::new("execute", |b| {
+            b.iter_with_setup(
+                || {
+                    let (testkit, wallets) = create_wallets(TRANSFERS_IN_BLOCK);
+                    let transfers: Vec<Box<dyn Transaction>> = wallets
+                        .iter()
+                        .map(|(sender, receiver)| {
+                            let transfer = sender.create_transfer(
+                                TRANSFER_AMOUNT,
+                                receiver.public_key(),
+                                ROLLBACK_DELAY,
+                            );
+                            Box::new(transfer) as Box<dyn Transaction>
+                        })
+                        .collect();
+                    (testkit, transfers)
+                },
+                |(mut testkit, transfers)| {
+                    let block = testkit.create_block_with_transactions(transfers);
+                    assert!(block.iter().all(|tx| tx.status().is_ok()));
+                },
+            )
+        })
+        .sample_size(10)
+        .throughput(Throughput::Elements(TRANSFERS_IN_BLOCK as u32)),
+    );
+}
+
+criterion_group!(benches, bench_transfers);
+criterion_main!(benches);
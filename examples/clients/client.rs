@@ -19,7 +19,8 @@ use exonum::{
 };
 use private_currency::{
     api::{CheckedWalletProof, FullEvent, TrustAnchor, WalletProof, WalletQuery},
-    transactions::{Accept, CreateWallet, Transfer},
+    client::{check_incoming_transfer, IncomingTransfer},
+    transactions::{Accept, CreateWallet, Reject, Transfer},
     SecretState, CONFIG,
 };
 use rand::{seq::sample_iter, thread_rng, Rng};
@@ -187,6 +188,13 @@ impl Client {
                         ));
                         self.state.rollback(transfer);
                     }
+                    FullEvent::ImplicitCreateWallet(ref transfer) => {
+                        self.log_info(&format!(
+                            "received event: `ImplicitCreateWallet`, tx_hash = {:?}",
+                            transfer.hash()
+                        ));
+                        self.state.initialize();
+                    }
                 }
 
                 self.log_info(&format!(
@@ -206,25 +214,30 @@ impl Client {
     }
 
     fn accept_transfers(&self, transfers: &[Transfer]) {
-        let accepts = transfers.iter().flat_map(|transfer| {
-            if let Some(verified) = self.state.verify_transfer(transfer) {
-                self.log_info(&format!(
-                    "received transfer: {}, tx_hash = {:?}",
-                    verified.value(),
-                    transfer.hash()
-                ));
-                Some(verified.accept)
-            } else {
-                self.log_error(&format!(
-                    "received incorrect transfer, tx_hash = {:?}",
-                    transfer.hash()
-                ));
-                None
+        for transfer in transfers {
+            match check_incoming_transfer(&self.state, transfer) {
+                Some(IncomingTransfer::Verified(verified)) => {
+                    self.log_info(&format!(
+                        "received transfer: {}, tx_hash = {:?}",
+                        verified.value(),
+                        transfer.hash()
+                    ));
+                    self.send_accept(&verified.accept);
+                }
+                Some(IncomingTransfer::Unverifiable(reject)) => {
+                    self.log_error(&format!(
+                        "received incorrect transfer, tx_hash = {:?}",
+                        transfer.hash()
+                    ));
+                    self.send_reject(&reject);
+                }
+                None => {
+                    self.log_error(&format!(
+                        "received unrelated transfer, tx_hash = {:?}",
+                        transfer.hash()
+                    ));
+                }
             }
-        });
-
-        for accept in accepts {
-            self.send_accept(&accept);
         }
     }
 
@@ -314,6 +327,22 @@ impl Client {
         assert_eq!(response, accept.hash());
     }
 
+    fn send_reject(&self, reject: &Reject) {
+        self.log_info(&format!(
+            "sending `Reject` for transfer {:?}, tx_hash = {:?}",
+            reject.transfer_id(),
+            reject.hash()
+        ));
+        let mut response = self
+            .http
+            .post(Self::TRANSACTION_URL)
+            .json(reject)
+            .send()
+            .expect("send `Reject`");
+        let response: Hash = response.json().expect("transaction hash");
+        assert_eq!(response, reject.hash());
+    }
+
     fn run(mut self) {
         let mut rng = thread_rng();
         let mut sleep = move || {
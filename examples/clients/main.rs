@@ -215,6 +215,7 @@ fn main() {
 
     let (service, debugger) = CurrencyService::debug(DebuggerOptions {
         check_invariants: true,
+        ..DebuggerOptions::default()
     });
     let debug_handle = thread::spawn(|| {
         for event in debugger {
@@ -228,6 +229,7 @@ fn main() {
                         height
                     );
                 }
+                DebugEvent::ExpiresSoon { .. } | DebugEvent::BlockCommitted(..) => {}
             }
         }
     });
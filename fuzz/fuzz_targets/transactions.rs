@@ -0,0 +1,38 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fuzz target feeding arbitrary transactions through `verify()` and `execute()`.
+//! See the crate docs for the input format.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate exonum_testkit;
+extern crate private_currency_fuzz;
+
+use exonum_testkit::TestKit;
+use private_currency_fuzz::{check_transactions, create_testkit, decode_input};
+
+use std::cell::RefCell;
+
+thread_local! {
+    // Creating a testkit is expensive, so it is reused across inputs. Each input
+    // is executed in a block that is reverted afterwards.
+    static TESTKIT: RefCell<TestKit> = RefCell::new(create_testkit());
+}
+
+fuzz_target!(|data: &[u8]| {
+    let messages = decode_input(data);
+    TESTKIT.with(|testkit| check_transactions(&mut testkit.borrow_mut(), messages));
+});
@@ -0,0 +1,156 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generates the seed corpus for the `transactions` fuzz target. Run with
+//!
+//! ```shell
+//! cargo run --bin seed_corpus -- corpus/transactions
+//! ```
+//!
+//! The seeds are valid transaction sequences for the fuzzing testkit, together with
+//! a few near-valid ones (with a corrupted field or truncated).
+
+extern crate exonum;
+extern crate private_currency;
+extern crate private_currency_fuzz;
+
+use exonum::{
+    crypto::SIGNATURE_LENGTH,
+    messages::{Message, RawMessage, HEADER_LENGTH},
+};
+use private_currency::transactions::{CustodialAcceptMany, DelegatedAccept};
+use private_currency_fuzz::{encode_input, keypair, wallet, SIGNERS};
+
+use std::{env, fs, path::Path};
+
+const ALICE: usize = 0;
+const BOB: usize = 1;
+/// Signer without a registered wallet.
+const CAROL: usize = SIGNERS - 1;
+
+/// Offset of the `amount` commitment in the `Transfer` body.
+const TRANSFER_AMOUNT_OFFSET: usize = 76;
+
+/// Copies the message flipping the bits of a byte at the specified offset in its body.
+fn corrupt(raw: &RawMessage, body_offset: usize) -> RawMessage {
+    let mut bytes = raw.as_ref().to_vec();
+    bytes[HEADER_LENGTH + body_offset] ^= 0xff;
+    RawMessage::from_vec(bytes)
+}
+
+/// Copies the message dropping the last `len` bytes of its body.
+fn truncate(raw: &RawMessage, len: usize) -> RawMessage {
+    let mut bytes = raw.as_ref().to_vec();
+    let signature_start = bytes.len() - SIGNATURE_LENGTH;
+    bytes.drain(signature_start - len..signature_start);
+    RawMessage::from_vec(bytes)
+}
+
+fn seeds() -> Vec<(&'static str, Vec<(usize, RawMessage)>)> {
+    let alice = wallet(ALICE);
+    let bob = wallet(BOB);
+    let carol = wallet(CAROL);
+    let (carol_pk, carol_sk) = keypair(CAROL);
+
+    let transfer = alice.create_transfer(1_000, bob.public_key(), 10);
+    let accept = bob.verify_transfer(&transfer).expect("verify").accept;
+    let delegated_accept =
+        DelegatedAccept::new(bob.public_key(), &transfer.hash(), &carol_pk, &carol_sk);
+    let custodial_accept =
+        CustodialAcceptMany::new(&carol_pk, vec![accept.raw().clone()], &carol_sk);
+
+    vec![
+        (
+            "create_wallet",
+            vec![(CAROL, carol.create_wallet().raw().clone())],
+        ),
+        ("transfer", vec![(ALICE, transfer.raw().clone())]),
+        (
+            "transfer_accept",
+            vec![(ALICE, transfer.raw().clone()), (BOB, accept.raw().clone())],
+        ),
+        (
+            "transfer_reject",
+            vec![
+                (ALICE, transfer.raw().clone()),
+                (BOB, bob.create_reject(&transfer).raw().clone()),
+            ],
+        ),
+        (
+            "accept_with_receipt",
+            vec![
+                (ALICE, transfer.raw().clone()),
+                (
+                    BOB,
+                    bob.create_accept_with_receipt(&transfer, b"invoice")
+                        .raw()
+                        .clone(),
+                ),
+            ],
+        ),
+        (
+            "two_phase_accept",
+            vec![
+                (ALICE, transfer.raw().clone()),
+                (BOB, bob.create_pre_accept(&transfer).raw().clone()),
+                (ALICE, alice.create_commit(&transfer).raw().clone()),
+            ],
+        ),
+        (
+            "delegated_accept",
+            vec![
+                (BOB, bob.delegate_acceptance(&carol_pk).raw().clone()),
+                (ALICE, transfer.raw().clone()),
+                (CAROL, delegated_accept.raw().clone()),
+            ],
+        ),
+        (
+            "custodial_accept",
+            vec![
+                (ALICE, transfer.raw().clone()),
+                (CAROL, custodial_accept.raw().clone()),
+            ],
+        ),
+        (
+            "register_alias",
+            vec![(ALICE, alice.register_alias("alice").raw().clone())],
+        ),
+        (
+            "transfer_to_unregistered",
+            vec![(
+                ALICE,
+                alice.create_transfer(1_000, &carol_pk, 10).raw().clone(),
+            )],
+        ),
+        (
+            "transfer_corrupted_amount",
+            vec![(ALICE, corrupt(transfer.raw(), TRANSFER_AMOUNT_OFFSET))],
+        ),
+        (
+            "transfer_truncated",
+            vec![(ALICE, truncate(transfer.raw(), 1))],
+        ),
+    ]
+}
+
+fn main() {
+    let dir = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "corpus/transactions".to_owned());
+    let dir = Path::new(&dir);
+    fs::create_dir_all(dir).expect("cannot create corpus directory");
+    for (name, transactions) in seeds() {
+        fs::write(dir.join(name), encode_input(&transactions)).expect("cannot write seed");
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by the fuzz targets and the corpus generator.
+//!
+//! # Input format
+//!
+//! A fuzz input is a sequence of records, each of which describes a single transaction:
+//!
+//! - signer index (1 byte), which selects one of [`SIGNERS`] fixed keypairs
+//!   (modulo `SIGNERS`)
+//! - length of the message (2 bytes, little-endian)
+//! - the message without the signature, i.e., the header followed by the body
+//!
+//! The harness fixes the payload length in the message header and signs the message
+//! with the selected key, so that mutated transactions pass signature verification
+//! and reach the deeper checks. A truncated trailing record is ignored.
+//!
+//! [`SIGNERS`]: constant.SIGNERS.html
+
+extern crate exonum;
+extern crate exonum_testkit;
+extern crate private_currency;
+
+use exonum::{
+    blockchain::{Schema as CoreSchema, Transaction, TransactionErrorType, TransactionSet},
+    crypto::{gen_keypair_from_seed, sign, PublicKey, SecretKey, Seed, SIGNATURE_LENGTH},
+    messages::{RawMessage, HEADER_LENGTH},
+};
+use exonum_testkit::{TestKit, TestKitBuilder};
+use private_currency::{SecretState, Service as Currency, Transactions};
+
+use std::collections::HashSet;
+
+/// Number of keypairs available for signing transactions. Wallets are registered
+/// for all signers except for the last one.
+pub const SIGNERS: usize = 3;
+
+/// Maximum number of transactions executed for a single input.
+pub const MAX_TRANSACTIONS: usize = 16;
+
+/// Returns a keypair of the signer with the specified index.
+pub fn keypair(signer: usize) -> (PublicKey, SecretKey) {
+    gen_keypair_from_seed(&Seed::new([signer as u8 + 1; 32]))
+}
+
+/// Returns the secret state of the signer with the specified index, as it appears
+/// in the testkit returned by [`create_testkit()`].
+///
+/// [`create_testkit()`]: fn.create_testkit.html
+pub fn wallet(signer: usize) -> SecretState {
+    let (verifying_key, signing_key) = keypair(signer);
+    let mut state = SecretState::from_keypair(verifying_key, signing_key);
+    if signer + 1 < SIGNERS {
+        state.initialize();
+    }
+    state
+}
+
+/// Creates a testkit with registered wallets. The state of the testkit is deterministic.
+pub fn create_testkit() -> TestKit {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Currency::default())
+        .create();
+    let create_wallets: Vec<Box<dyn Transaction>> = (0..SIGNERS - 1)
+        .map(|signer| Box::new(wallet(signer).create_wallet()) as Box<dyn Transaction>)
+        .collect();
+    testkit.create_block_with_transactions(create_wallets);
+    testkit
+}
+
+/// Encodes signed transactions as a fuzz input.
+pub fn encode_input(transactions: &[(usize, RawMessage)]) -> Vec<u8> {
+    let mut input = vec![];
+    for (signer, raw) in transactions {
+        let unsigned = &raw.as_ref()[..raw.len() - SIGNATURE_LENGTH];
+        input.push(*signer as u8);
+        input.push(unsigned.len() as u8);
+        input.push((unsigned.len() >> 8) as u8);
+        input.extend_from_slice(unsigned);
+    }
+    input
+}
+
+/// Decodes a fuzz input into signed messages.
+pub fn decode_input(mut input: &[u8]) -> Vec<RawMessage> {
+    let mut messages = vec![];
+    while input.len() >= 3 && messages.len() < MAX_TRANSACTIONS {
+        let signer = usize::from(input[0]) % SIGNERS;
+        let len = usize::from(input[1]) | usize::from(input[2]) << 8;
+        input = &input[3..];
+        if input.len() < len {
+            break;
+        }
+        let (message, rest) = input.split_at(len);
+        input = rest;
+        if message.len() < HEADER_LENGTH {
+            continue;
+        }
+
+        let mut message = message.to_vec();
+        let payload_length = (message.len() + SIGNATURE_LENGTH) as u32;
+        for (i, byte) in message[6..10].iter_mut().enumerate() {
+            *byte = (payload_length >> (8 * i)) as u8;
+        }
+        let signature = sign(&message, &keypair(signer).1);
+        message.extend_from_slice(signature.as_ref());
+        messages.push(RawMessage::from_vec(message));
+    }
+    messages
+}
+
+/// Includes the messages parsed as service transactions into a new block on top
+/// of the testkit state, and then reverts the block.
+///
+/// Transactions failing stateless verification are not included into the block.
+/// The stateless verification must not panic, and the included transactions must either
+/// execute successfully or fail with an execution error.
+///
+/// # Panics
+///
+/// Panics if any of the transactions panics during verification or execution.
+pub fn check_transactions(testkit: &mut TestKit, messages: Vec<RawMessage>) {
+    let snapshot = testkit.snapshot();
+    let committed = CoreSchema::new(&snapshot).transactions();
+    let mut hashes = HashSet::new();
+    let transactions: Vec<Box<dyn Transaction>> = messages
+        .into_iter()
+        .filter_map(|raw| Transactions::tx_from_raw(raw).ok())
+        .map(Into::into)
+        .filter(|tx: &Box<dyn Transaction>| {
+            // The testkit does not accept committed or duplicate transactions.
+            let hash = tx.hash();
+            !committed.contains(&hash) && hashes.insert(hash)
+        })
+        .collect();
+
+    testkit.checkpoint();
+    let block = testkit.create_block_with_transactions(transactions);
+    for tx in block.iter() {
+        if let Err(e) = tx.status() {
+            assert_ne!(
+                e.error_type(),
+                TransactionErrorType::Panic,
+                "transaction {:?} panicked: {:?}",
+                tx.content(),
+                e
+            );
+        }
+    }
+    testkit.rollback();
+}
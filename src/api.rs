@@ -18,6 +18,7 @@ use exonum::{
     api::{self, ServiceApiState},
     blockchain::{Block, BlockProof, Blockchain, Schema as CoreSchema, Transaction},
     crypto::{CryptoHash, Hash, PublicKey},
+    helpers::Height,
     storage::{
         proof_list_index::ListProofError,
         proof_map_index::{MapProofError, ProofMapKey},
@@ -25,11 +26,16 @@ use exonum::{
     },
 };
 
-use std::{collections::HashSet, fmt};
+use std::{collections::HashSet, fmt, sync::Arc};
 
-use super::SERVICE_ID;
-use storage::{maybe_create_wallet, maybe_transfer, Event, EventTag, Schema, Wallet};
-use transactions::{CreateWallet, CryptoTransactions, Transfer};
+use super::{Config, SERVICE_ID};
+use crypto::enc;
+use debug::ExportedBlock;
+use storage::{
+    alias_key, maybe_create_wallet, maybe_transfer, Event, EventTag, Schema, Wallet,
+    ALIASES_TABLE_INDEX, WALLETS_TABLE_INDEX,
+};
+use transactions::{ConfiguredTransaction, CreateWallet, CryptoTransactions, Transfer};
 
 pub use utils::{BlockVerifyError, TrustAnchor};
 
@@ -46,6 +52,214 @@ pub struct WalletQuery {
     pub start_history_at: u64,
 }
 
+/// Query for the `wallet/encryption-key` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionKeyQuery {
+    /// Public key of the account.
+    pub key: PublicKey,
+}
+
+/// Encryption key of a wallet, as returned by the `wallet/encryption-key` endpoint.
+///
+/// Senders should use the key to [encrypt] the opening of the transferred amount
+/// for the receiver.
+///
+/// [encrypt]: ::EncryptedData
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionKey {
+    /// Ed25519 public key of the account.
+    pub key: PublicKey,
+    /// Curve25519 public key used to encrypt data for the account owner. Serialized
+    /// as a hex string.
+    #[serde(with = "hex_encryption_key")]
+    pub encryption_key: enc::PublicKey,
+}
+
+mod hex_encryption_key {
+    use exonum::encoding::serialize::{decode_hex, encode_hex};
+    use serde::{de::Error as DeError, Deserialize, Deserializer, Serializer};
+
+    use crypto::enc::PublicKey;
+
+    pub fn serialize<S: Serializer>(key: &PublicKey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_hex(&key[..]))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PublicKey, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let bytes = decode_hex(&hex).map_err(D::Error::custom)?;
+        PublicKey::from_slice(&bytes).ok_or_else(|| D::Error::custom("invalid key length"))
+    }
+}
+
+/// Query for the `wallet/pending-transfers` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingTransfersQuery {
+    /// Public key of the receiver.
+    pub key: PublicKey,
+}
+
+/// Unaccepted incoming transfer, as returned by the `wallet/pending-transfers` endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingTransfer {
+    /// Hash of the transfer.
+    pub id: Hash,
+    /// Height of the block in which the transfer is rolled back unless it is accepted
+    /// in this or an earlier block.
+    pub rollback_height: Height,
+    /// Number of blocks, starting from the next one, in which the transfer can still
+    /// be accepted.
+    pub remaining_blocks: u64,
+}
+
+/// Query for the `debug/signing-preimage` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningPreimageQuery {
+    /// Hex-encoded unsigned transaction, i.e., the message header followed by the payload,
+    /// without the trailing signature.
+    pub unsigned_tx: String,
+}
+
+/// Query for the `wallets/proofs` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletsQuery {
+    /// Public keys of the accounts to check.
+    pub keys: Vec<PublicKey>,
+}
+
+/// Query for the `transfer` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferQuery {
+    /// Hash of the transfer transaction.
+    pub id: Hash,
+}
+
+/// Query for the `debug/block` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockQuery {
+    /// Height of the block to export.
+    pub height: Height,
+}
+
+/// Query for the `alias` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasQuery {
+    /// Alias to resolve.
+    pub alias: String,
+}
+
+/// Processing stage of a committed transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransferStage {
+    /// Execution of the transfer has failed; the transfer did not affect wallet balances.
+    Failed,
+    /// The transfer awaits acceptance from the receiver.
+    Pending,
+    /// The transfer is pre-accepted by the receiver and awaits a `Commit` from the sender.
+    PreAccepted,
+    /// The transfer has been accepted by the receiver.
+    Accepted,
+    /// The transfer has not been accepted in time and was refunded to the sender.
+    RolledBack,
+    /// The transfer has been rejected by the receiver and was refunded to the sender.
+    Rejected,
+}
+
+impl TransferStage {
+    /// Checks if the outcome of the transfer is known, i.e., it is neither pending
+    /// nor pre-accepted.
+    pub fn is_settled(self) -> bool {
+        match self {
+            TransferStage::Pending | TransferStage::PreAccepted => false,
+            _ => true,
+        }
+    }
+}
+
+/// Status of a committed transfer, as returned by the `transfer` endpoint.
+///
+/// # Finality
+///
+/// A transfer is considered final if it is [settled] and it has at least [`finality_depth`]
+/// confirmations. A pending or pre-accepted transfer is never final, since its outcome depends
+/// on whether it is accepted within the rollback delay.
+///
+/// [settled]: TransferStage::is_settled()
+/// [`finality_depth`]: ::Config::finality_depth
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferStatus {
+    /// Height of the block containing the transfer.
+    pub height: Height,
+    /// Number of blocks committed on top of the block containing the transfer.
+    pub confirmations: u64,
+    /// Number of confirmations necessary for the transfer to become final.
+    pub finality_depth: u64,
+    /// Processing stage of the transfer.
+    pub stage: TransferStage,
+    /// Whether the transfer may be treated as final.
+    pub is_final: bool,
+}
+
+impl TransferStatus {
+    /// Retrieves the status of a transfer from a storage snapshot.
+    ///
+    /// # Return value
+    ///
+    /// Returns `None` if a transfer with the specified hash is not committed.
+    fn new<T: AsRef<dyn Snapshot>>(snapshot: T, id: &Hash, finality_depth: u64) -> Option<Self> {
+        let transfer = maybe_transfer(&snapshot, id)?;
+        let core_schema = CoreSchema::new(&snapshot);
+        let height = core_schema.transactions_locations().get(id)?.block_height();
+        let confirmations = core_schema.height().0 - height.0;
+
+        let schema = Schema::new(&snapshot);
+        let has_failed = core_schema
+            .transaction_results()
+            .get(id)
+            .map_or(false, |result| result.is_err());
+        let stage = if has_failed {
+            TransferStage::Failed
+        } else if schema.pre_accepted_transfers().contains(id) {
+            TransferStage::PreAccepted
+        } else if schema.unaccepted_transfers_index(transfer.to()).contains(id) {
+            TransferStage::Pending
+        } else if schema.accepting_event(id).is_some() {
+            TransferStage::Accepted
+        } else if schema.is_rejected(id) {
+            TransferStage::Rejected
+        } else {
+            TransferStage::RolledBack
+        };
+
+        Some(TransferStatus {
+            height,
+            confirmations,
+            finality_depth,
+            stage,
+            is_final: stage.is_settled() && confirmations >= finality_depth,
+        })
+    }
+}
+
+/// Components of the service state, as returned by the `debug/state-hash` endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateHashComponents {
+    /// Components of the service state hash, in the same order as in the state hash.
+    ///
+    /// See [`Schema::state_hash_components()`] for details.
+    ///
+    /// [`Schema::state_hash_components()`]: ::storage::Schema::state_hash_components()
+    pub state_hash: Vec<(String, Hash)>,
+    /// Digests of the tables that are not directly committed to by the state hash.
+    /// These digests are not a part of consensus.
+    ///
+    /// See [`Schema::auxiliary_hash_components()`] for details.
+    ///
+    /// [`Schema::auxiliary_hash_components()`]: ::storage::Schema::auxiliary_hash_components()
+    pub auxiliary: Vec<(String, Hash)>,
+}
+
 /// Event changing balance of a wallet.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename = "kebab-case")]
@@ -64,6 +278,19 @@ pub enum FullEvent {
 
     /// Rolled-back transfer returning the funds to the sender.
     Rollback(Transfer),
+
+    /// Event corresponding to wallet creation by an incoming transfer to an unregistered key.
+    /// Like [`CreateWallet`], this can only be the very first event in wallet history;
+    /// the wallet is initialized in the same way. Such events occur only if
+    /// [`auto_create_receivers`] is enabled in the service configuration.
+    ///
+    /// Note that the enclosed transfer is not credited to the wallet by this event;
+    /// it still needs to be [`Accept`]ed.
+    ///
+    /// [`CreateWallet`]: #variant.CreateWallet
+    /// [`auto_create_receivers`]: ::Config::auto_create_receivers
+    /// [`Accept`]: ::transactions::Accept
+    ImplicitCreateWallet(Transfer),
 }
 
 impl FullEvent {
@@ -81,6 +308,9 @@ impl FullEvent {
             tag if tag == EventTag::Rollback as u8 => {
                 FullEvent::Rollback(maybe_transfer(snapshot, id).expect("Transfer"))
             }
+            tag if tag == EventTag::ImplicitCreateWallet as u8 => {
+                FullEvent::ImplicitCreateWallet(maybe_transfer(snapshot, id).expect("Transfer"))
+            }
             _ => unreachable!(),
         }
     }
@@ -90,6 +320,7 @@ impl FullEvent {
             FullEvent::CreateWallet(..) => EventTag::CreateWallet,
             FullEvent::Transfer(..) => EventTag::Transfer,
             FullEvent::Rollback(..) => EventTag::Rollback,
+            FullEvent::ImplicitCreateWallet(..) => EventTag::ImplicitCreateWallet,
         }
     }
 
@@ -103,6 +334,7 @@ impl FullEvent {
             FullEvent::CreateWallet(tx) => tx.hash(),
             FullEvent::Transfer(tx) => tx.hash(),
             FullEvent::Rollback(tx) => tx.hash(),
+            FullEvent::ImplicitCreateWallet(tx) => tx.hash(),
         };
         hash == *event.transaction_hash()
     }
@@ -231,6 +463,10 @@ pub enum ProofDescription {
     History,
     /// `MapProof` for unaccepted transfers.
     UnacceptedTransfers,
+    /// `MapProof` from the `state_hash` mentioned in the block header, to the aliases table.
+    AliasesTable,
+    /// `MapProof` from the aliases table to a specific alias.
+    Alias,
 }
 
 impl fmt::Display for ProofDescription {
@@ -242,6 +478,8 @@ impl fmt::Display for ProofDescription {
             Wallet => f.write_str("wallet"),
             History => f.write_str("history"),
             UnacceptedTransfers => f.write_str("unaccepted transfers"),
+            AliasesTable => f.write_str("aliases table"),
+            Alias => f.write_str("alias"),
         }
     }
 }
@@ -259,7 +497,8 @@ impl WalletProof {
         let block_proof = core_schema
             .block_and_precommits(core_schema.height())
             .expect("BlockProof");
-        let wallet_table_proof = core_schema.get_proof_to_service_table(SERVICE_ID, 0);
+        let wallet_table_proof =
+            core_schema.get_proof_to_service_table(SERVICE_ID, WALLETS_TABLE_INDEX);
 
         let schema = Schema::new(&snapshot);
         let wallets = schema.wallets();
@@ -322,7 +561,7 @@ impl WalletProof {
         let wallets_hash: Option<Hash> = Self::check_map_proof_with_single_key(
             self.wallet_table_proof.clone(),
             *self.block_proof.block.state_hash(),
-            &Blockchain::service_table_unique_key(SERVICE_ID, 0),
+            &Blockchain::service_table_unique_key(SERVICE_ID, WALLETS_TABLE_INDEX),
             ProofDescription::WalletsTable,
         )?;
         // The key corresponding to the wallets table cannot be missing.
@@ -474,6 +713,170 @@ impl WalletContentsProof {
     }
 }
 
+/// Cryptographically authenticated proof of the state for multiple wallets.
+///
+/// Unlike [`WalletProof`], this proof covers only wallet summaries, but not wallet histories
+/// or unaccepted transfers. The summaries for all wallets are authenticated with a single
+/// `MapProof`, which is much more compact than separate proofs for each wallet.
+///
+/// [`WalletProof`]: self::WalletProof
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletsProof {
+    block_proof: BlockProof,
+    wallet_table_proof: MapProof<Hash, Hash>,
+    wallets_proof: MapProof<PublicKey, Wallet>,
+}
+
+/// Information about wallets obtained after checking a `WalletsProof`.
+#[derive(Debug)]
+pub struct CheckedWalletsProof {
+    /// Block information.
+    pub block: Block,
+    /// Wallets in the same order as keys in the query. `None` signifies that the wallet
+    /// with the corresponding key does not exist.
+    pub wallets: Vec<Option<Wallet>>,
+}
+
+impl WalletsProof {
+    /// Maximum number of wallets in a single proof.
+    pub const MAX_WALLETS: usize = 1_000;
+
+    /// Creates a new proof based on a given storage snapshot.
+    fn new<T: AsRef<dyn Snapshot>>(snapshot: T, query: &WalletsQuery) -> Self {
+        let core_schema = CoreSchema::new(&snapshot);
+        let block_proof = core_schema
+            .block_and_precommits(core_schema.height())
+            .expect("BlockProof");
+        let wallet_table_proof =
+            core_schema.get_proof_to_service_table(SERVICE_ID, WALLETS_TABLE_INDEX);
+        let wallets_proof = Schema::new(&snapshot)
+            .wallets()
+            .get_multiproof(query.keys.iter().cloned());
+
+        WalletsProof {
+            block_proof,
+            wallet_table_proof,
+            wallets_proof,
+        }
+    }
+
+    /// Checks the proof, returning wallets for all keys in the `query`.
+    pub fn check(
+        &self,
+        trust_anchor: &TrustAnchor,
+        query: &WalletsQuery,
+    ) -> Result<CheckedWalletsProof, VerifyError> {
+        trust_anchor.verify_block_proof(&self.block_proof)?;
+
+        let wallets_hash: Option<Hash> = WalletProof::check_map_proof_with_single_key(
+            self.wallet_table_proof.clone(),
+            *self.block_proof.block.state_hash(),
+            &Blockchain::service_table_unique_key(SERVICE_ID, WALLETS_TABLE_INDEX),
+            ProofDescription::WalletsTable,
+        )?;
+        let wallets_hash =
+            wallets_hash.ok_or(VerifyError::MissingKey(ProofDescription::WalletsTable))?;
+
+        let checked = self
+            .wallets_proof
+            .clone()
+            .check()
+            .map_err(|error| VerifyError::MapProof {
+                error,
+                proof_description: ProofDescription::Wallet,
+            })?;
+        if checked.merkle_root() != wallets_hash {
+            return Err(VerifyError::ProofDisconnect(ProofDescription::Wallet));
+        }
+
+        let entries = checked.all_entries();
+        let wallets = query
+            .keys
+            .iter()
+            .map(|key| {
+                entries
+                    .iter()
+                    .find(|&&(k, _)| k == key)
+                    .map(|&(_, wallet)| wallet.cloned())
+                    .ok_or(VerifyError::MissingKey(ProofDescription::Wallet))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(CheckedWalletsProof {
+            block: self.block_proof.block.clone(),
+            wallets,
+        })
+    }
+}
+
+/// Cryptographically authenticated proof of the wallet key registered for an alias,
+/// or of the absence of such a key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AliasProof {
+    block_proof: BlockProof,
+    alias_table_proof: MapProof<Hash, Hash>,
+    alias_proof: MapProof<Hash, PublicKey>,
+}
+
+/// Information about an alias obtained after checking an `AliasProof`.
+#[derive(Debug)]
+pub struct CheckedAliasProof {
+    /// Block information.
+    pub block: Block,
+    /// Public key of the wallet owning the alias, or `None` if the alias is not registered.
+    pub key: Option<PublicKey>,
+}
+
+impl AliasProof {
+    /// Creates a new proof based on a given storage snapshot.
+    fn new<T: AsRef<dyn Snapshot>>(snapshot: T, query: &AliasQuery) -> Self {
+        let core_schema = CoreSchema::new(&snapshot);
+        let block_proof = core_schema
+            .block_and_precommits(core_schema.height())
+            .expect("BlockProof");
+        let alias_table_proof =
+            core_schema.get_proof_to_service_table(SERVICE_ID, ALIASES_TABLE_INDEX);
+        let alias_proof = Schema::new(&snapshot)
+            .aliases()
+            .get_proof(alias_key(&query.alias));
+
+        AliasProof {
+            block_proof,
+            alias_table_proof,
+            alias_proof,
+        }
+    }
+
+    /// Checks the proof, returning the key registered for the alias in the `query`.
+    pub fn check(
+        &self,
+        trust_anchor: &TrustAnchor,
+        query: &AliasQuery,
+    ) -> Result<CheckedAliasProof, VerifyError> {
+        trust_anchor.verify_block_proof(&self.block_proof)?;
+
+        let aliases_hash: Option<Hash> = WalletProof::check_map_proof_with_single_key(
+            self.alias_table_proof.clone(),
+            *self.block_proof.block.state_hash(),
+            &Blockchain::service_table_unique_key(SERVICE_ID, ALIASES_TABLE_INDEX),
+            ProofDescription::AliasesTable,
+        )?;
+        let aliases_hash =
+            aliases_hash.ok_or(VerifyError::MissingKey(ProofDescription::AliasesTable))?;
+        let key = WalletProof::check_map_proof_with_single_key(
+            self.alias_proof.clone(),
+            aliases_hash,
+            &alias_key(&query.alias),
+            ProofDescription::Alias,
+        )?;
+
+        Ok(CheckedAliasProof {
+            block: self.block_proof.block.clone(),
+            key,
+        })
+    }
+}
+
 // Required for conversions in `Service::wire`.
 #[cfg_attr(feature = "cargo-clippy", allow(clippy::needless_pass_by_value))]
 impl Api {
@@ -485,11 +888,185 @@ impl Api {
         Ok(WalletProof::new(snapshot, &query))
     }
 
+    /// Returns information about several wallets at once, authenticated with a single
+    /// cryptographic proof. At most [`WalletsProof::MAX_WALLETS`] keys may be queried at once.
+    ///
+    /// [`WalletsProof::MAX_WALLETS`]: self::WalletsProof::MAX_WALLETS
+    pub fn wallets(state: &ServiceApiState, query: WalletsQuery) -> api::Result<WalletsProof> {
+        if query.keys.len() > WalletsProof::MAX_WALLETS {
+            return Err(api::Error::BadRequest(format!(
+                "too many keys in query; at most {} are allowed",
+                WalletsProof::MAX_WALLETS
+            )));
+        }
+        let snapshot = state.snapshot();
+        Ok(WalletsProof::new(snapshot, &query))
+    }
+
+    /// Returns the key that should be used to encrypt data for the owner of a wallet.
+    ///
+    /// The encryption key is derived from the Ed25519 key of the wallet; this endpoint
+    /// allows clients not to reimplement the derivation.
+    pub fn encryption_key(
+        state: &ServiceApiState,
+        query: EncryptionKeyQuery,
+    ) -> api::Result<EncryptionKey> {
+        let snapshot = state.snapshot();
+        let wallet = Schema::new(snapshot)
+            .wallet(&query.key)
+            .ok_or_else(|| api::Error::NotFound("wallet not found".to_owned()))?;
+        Ok(EncryptionKey {
+            key: query.key,
+            encryption_key: wallet.encryption_key(),
+        })
+    }
+
+    /// Returns unaccepted incoming transfers of a wallet together with the number of blocks
+    /// in which each transfer can still be accepted. Transfers are ordered by their
+    /// rollback height.
+    pub fn pending_transfers(
+        state: &ServiceApiState,
+        query: PendingTransfersQuery,
+    ) -> api::Result<Vec<PendingTransfer>> {
+        let snapshot = state.snapshot();
+        let schema = Schema::new(&snapshot);
+        if schema.wallet(&query.key).is_none() {
+            return Err(api::Error::NotFound("wallet not found".to_owned()));
+        }
+
+        let height = CoreSchema::new(&snapshot).height();
+        let mut transfers: Vec<_> = schema
+            .unaccepted_transfers_index(&query.key)
+            .keys()
+            .filter_map(|id| {
+                // Transfers scheduled for rollback at height `h` are rolled back
+                // in the block at height `h + 1`.
+                let rollback_height = schema.scheduled_rollback_height(&id)?.next();
+                Some(PendingTransfer {
+                    id,
+                    rollback_height,
+                    remaining_blocks: rollback_height.0 - height.0,
+                })
+            })
+            .collect();
+        transfers.sort_by_key(|transfer| transfer.rollback_height);
+        Ok(transfers)
+    }
+
+    /// Resolves an alias into the key of the wallet owning it. The result is supported
+    /// with cryptographic proofs.
+    pub fn alias(state: &ServiceApiState, query: AliasQuery) -> api::Result<AliasProof> {
+        let snapshot = state.snapshot();
+        Ok(AliasProof::new(snapshot, &query))
+    }
+
+    /// Returns the status of a committed transfer, including the number of confirmations
+    /// for it.
+    pub fn transfer(
+        config: &Config,
+        state: &ServiceApiState,
+        query: TransferQuery,
+    ) -> api::Result<TransferStatus> {
+        let snapshot = state.snapshot();
+        TransferStatus::new(snapshot, &query.id, config.finality_depth)
+            .ok_or_else(|| api::Error::NotFound("transfer not found".to_owned()))
+    }
+
+    /// Returns the service configuration. Clients may use it to choose parameters
+    /// of transactions, e.g., the default rollback delay for transfers.
+    pub fn config(config: &Config, _state: &ServiceApiState, _query: ()) -> api::Result<Config> {
+        Ok(config.clone())
+    }
+
+    /// Returns the components of the service state hash and digests of the tables
+    /// not covered by it, together with the names of the corresponding tables.
+    /// The endpoint is intended for debugging and is only available via the private API.
+    pub fn state_hash_components(
+        config: &Config,
+        state: &ServiceApiState,
+        _query: (),
+    ) -> api::Result<StateHashComponents> {
+        let schema = Schema::new(state.snapshot());
+        Ok(StateHashComponents {
+            state_hash: schema.state_hash_components(),
+            auxiliary: schema.auxiliary_hash_components(config),
+        })
+    }
+
+    /// Exports the latest committed block, so that the service state can be rebuilt
+    /// from the exported blocks with [`replay_blocks`]. The endpoint is only available
+    /// via the private API.
+    ///
+    /// [`replay_blocks`]: ::replay_blocks
+    pub fn latest_block(state: &ServiceApiState, _query: ()) -> api::Result<ExportedBlock> {
+        Ok(ExportedBlock::latest(state.snapshot().as_ref()))
+    }
+
+    /// Exports the committed block at the specified height. Unlike [`latest_block`],
+    /// the endpoint can be used to export earlier blocks, although without the service
+    /// state hash; the state hash in the block header is checked by [`replay_blocks`]
+    /// nonetheless. The endpoint is only available via the private API.
+    ///
+    /// [`latest_block`]: #method.latest_block
+    /// [`replay_blocks`]: ::replay_blocks
+    pub fn block(state: &ServiceApiState, query: BlockQuery) -> api::Result<ExportedBlock> {
+        ExportedBlock::at_height(state.snapshot().as_ref(), query.height)
+            .ok_or_else(|| api::Error::NotFound("block not found".to_owned()))
+    }
+
+    /// Returns the hex-encoded bytes that need to be signed for an unsigned transaction
+    /// in order for its signature to verify. The endpoint is intended for developers
+    /// of clients in other languages and is only available via the private API.
+    ///
+    /// The returned bytes are the unsigned transaction with the payload length
+    /// in the message header set to the length of the signed message (i.e., including
+    /// the signature). The endpoint fails if the transaction cannot be parsed as one
+    /// of [`CryptoTransactions`].
+    ///
+    /// [`CryptoTransactions`]: ::transactions::CryptoTransactions
+    pub fn signing_preimage(
+        _state: &ServiceApiState,
+        query: SigningPreimageQuery,
+    ) -> api::Result<String> {
+        use byteorder::{ByteOrder, LittleEndian};
+        use exonum::{
+            blockchain::TransactionSet,
+            crypto::SIGNATURE_LENGTH,
+            encoding::serialize::{decode_hex, encode_hex},
+            messages::{RawMessage, HEADER_LENGTH},
+        };
+
+        let mut preimage: Vec<u8> =
+            decode_hex(&query.unsigned_tx).map_err(|e| api::Error::BadRequest(e.to_string()))?;
+        if preimage.len() < HEADER_LENGTH {
+            return Err(api::Error::BadRequest(
+                "transaction is shorter than message header".to_owned(),
+            ));
+        }
+        let signed_len = preimage.len() + SIGNATURE_LENGTH;
+        LittleEndian::write_u32(&mut preimage[6..HEADER_LENGTH], signed_len as u32);
+
+        // Check that the transaction is well-formed by parsing it with a dummy signature.
+        let mut raw = preimage.clone();
+        raw.resize(signed_len, 0);
+        CryptoTransactions::tx_from_raw(RawMessage::from_vec(raw))
+            .map_err(|e| api::Error::BadRequest(e.to_string()))?;
+        Ok(encode_hex(&preimage))
+    }
+
     /// Accepts transactions for processing.
-    pub fn transaction(state: &ServiceApiState, tx: CryptoTransactions) -> api::Result<Hash> {
+    pub fn transaction(
+        config: &Arc<Config>,
+        state: &ServiceApiState,
+        tx: CryptoTransactions,
+    ) -> api::Result<Hash> {
         use exonum::node::TransactionSend;
 
-        let tx: Box<dyn Transaction> = tx.into();
+        config
+            .enabled_transactions
+            .check(&tx)
+            .map_err(|e| api::Error::BadRequest(e.to_string()))?;
+        let tx: Box<dyn Transaction> = Box::new(ConfiguredTransaction::new(tx, Arc::clone(config)));
         let tx_hash = tx.hash();
         state
             .sender()
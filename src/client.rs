@@ -0,0 +1,326 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client-side helpers.
+
+use api::{AliasProof, AliasQuery, TrustAnchor, VerifyError};
+use crypto::Commitment;
+use secrets::{SecretState, VerifiedTransfer};
+use transactions::{Reject, Transfer};
+
+/// Maximum supported number of decimal places. `10^19` is the greatest power of 10
+/// fitting into `u64`.
+const MAX_DECIMALS: u8 = 19;
+
+/// Error parsing a decimal amount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Fail)]
+pub enum AmountError {
+    /// The amount is empty, or its integer or fractional part is empty.
+    #[fail(display = "the amount or its part is empty")]
+    Empty,
+
+    /// The amount contains a char other than decimal digits and a single decimal point.
+    #[fail(display = "the amount contains an invalid char")]
+    InvalidChar,
+
+    /// The amount has more decimal places than allowed.
+    #[fail(display = "the amount has more than {} decimal places", decimals)]
+    ExcessPrecision {
+        /// Allowed number of decimal places.
+        decimals: u8,
+    },
+
+    /// The amount in atomic units does not fit into `u64`.
+    #[fail(display = "the amount is too large")]
+    Overflow,
+}
+
+/// Conversion between decimal amounts (e.g., `"123.45"`) used by humans and integer amounts
+/// in atomic units used by the service.
+///
+/// # Examples
+///
+/// ```
+/// # use private_currency::client::{AmountError, Decimals};
+/// let decimals = Decimals::new(2);
+/// assert_eq!(decimals.parse("123.45"), Ok(12_345));
+/// assert_eq!(decimals.parse("1.5"), Ok(150));
+/// assert_eq!(
+///     decimals.parse("123.456"),
+///     Err(AmountError::ExcessPrecision { decimals: 2 })
+/// );
+/// assert_eq!(decimals.format(12_345), "123.45");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimals {
+    decimals: u8,
+    scale: u64,
+}
+
+impl Decimals {
+    /// Creates a conversion with the specified number of decimal places.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `decimals` exceeds 19, since in this case one unit cannot be represented
+    /// in atomic units.
+    pub fn new(decimals: u8) -> Self {
+        assert!(
+            decimals <= MAX_DECIMALS,
+            "too many decimal places: {}",
+            decimals
+        );
+        Decimals {
+            decimals,
+            scale: 10_u64.pow(u32::from(decimals)),
+        }
+    }
+
+    /// Returns the number of decimal places.
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    /// Parses a decimal amount into atomic units.
+    ///
+    /// The amount must consist of decimal digits with an optional decimal point; signs,
+    /// whitespace, digit separators and exponents are not allowed. The fractional part
+    /// may be shorter than the number of decimal places, but not longer.
+    pub fn parse(&self, amount: &str) -> Result<u64, AmountError> {
+        let mut parts = amount.splitn(2, '.');
+        let integer = parts.next().unwrap_or_default();
+        let fraction = parts.next();
+
+        let integer = parse_digits(integer)?;
+        let fraction = match fraction {
+            Some(fraction) => {
+                if fraction.len() > usize::from(self.decimals) {
+                    // Distinguish excess precision from garbage in the fractional part.
+                    parse_digits(fraction)?;
+                    return Err(AmountError::ExcessPrecision {
+                        decimals: self.decimals,
+                    });
+                }
+                let padding = u32::from(self.decimals) - fraction.len() as u32;
+                parse_digits(fraction)? * 10_u64.pow(padding)
+            }
+            None => 0,
+        };
+
+        integer
+            .checked_mul(self.scale)
+            .and_then(|value| value.checked_add(fraction))
+            .ok_or(AmountError::Overflow)
+    }
+
+    /// Formats an amount in atomic units as a decimal. The fractional part of the output
+    /// always has the configured number of decimal places.
+    pub fn format(&self, amount: u64) -> String {
+        if self.decimals == 0 {
+            return amount.to_string();
+        }
+        format!(
+            "{}.{:0width$}",
+            amount / self.scale,
+            amount % self.scale,
+            width = usize::from(self.decimals)
+        )
+    }
+}
+
+/// Parses a non-empty sequence of decimal digits.
+fn parse_digits(digits: &str) -> Result<u64, AmountError> {
+    if digits.is_empty() {
+        return Err(AmountError::Empty);
+    }
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(AmountError::InvalidChar);
+    }
+    digits.parse().map_err(|_| AmountError::Overflow)
+}
+
+/// Result of checking an incoming transfer by its receiver.
+#[derive(Debug)]
+pub enum IncomingTransfer {
+    /// The transferred amount was successfully decrypted and matches the commitment
+    /// in the transfer. The receiver may accept the transfer.
+    Verified(VerifiedTransfer),
+    /// The transferred amount cannot be decrypted (e.g., because the sender has used a wrong
+    /// encryption key), or it does not match the commitment in the transfer. The receiver
+    /// cannot know the amount it would be credited with, so it should not accept the transfer.
+    ///
+    /// The receiver may send the enclosed `Reject` transaction to refund the transfer
+    /// immediately, or it may ignore the transfer, in which case it will be refunded once
+    /// its rollback delay expires.
+    Unverifiable(Reject),
+}
+
+/// Checks an incoming transfer on behalf of its receiver.
+///
+/// # Return value
+///
+/// Returns `None` if `receiver` is not the receiver of the `transfer`.
+pub fn check_incoming_transfer(
+    receiver: &SecretState,
+    transfer: &Transfer,
+) -> Option<IncomingTransfer> {
+    if transfer.to() != receiver.public_key() {
+        return None;
+    }
+    Some(match receiver.verify_transfer(transfer) {
+        Some(verified) => IncomingTransfer::Verified(verified),
+        None => IncomingTransfer::Unverifiable(receiver.create_reject(transfer)),
+    })
+}
+
+/// Checks that accepting the `transfer` has credited the receiver with exactly
+/// the transferred amount.
+///
+/// The check is homomorphic: the difference between the balance commitments before
+/// and after the acceptance must be equal to the commitment in the transfer. Thus, it does not
+/// require knowing openings for the balances.
+///
+/// `balance_before` and `balance_after` are the balances of the receiver’s wallet
+/// immediately before and after the acceptance. Acceptance is recorded as a separate entry
+/// in the wallet history, so the balances may be obtained with [`Schema::past_balance()`]
+/// at the indexes preceding and corresponding to the acceptance event. However, past balances
+/// are only retained starting from the latest outgoing transfer of the wallet; once
+/// the receiver sends a transfer, balances for earlier events are removed, and the check
+/// can no longer be performed with them.
+///
+/// [`Schema::past_balance()`]: ::storage::Schema::past_balance()
+pub fn is_accepted_balance(
+    balance_before: &Commitment,
+    balance_after: &Commitment,
+    transfer: &Transfer,
+) -> bool {
+    balance_after - balance_before == transfer.amount()
+}
+
+/// Error creating a transfer to an alias.
+#[derive(Debug, Fail)]
+pub enum AliasTransferError {
+    /// The proof for the alias is invalid.
+    #[fail(display = "alias proof verification failed: {}", _0)]
+    Proof(#[fail(cause)] VerifyError),
+
+    /// The alias is not registered.
+    #[fail(display = "the alias is not registered")]
+    UnknownAlias,
+}
+
+/// Creates a transfer to the wallet owning an alias.
+///
+/// The alias is resolved with the `proof` returned by the `alias` endpoint of the service
+/// HTTP API for the `query`. The proof is checked against the `trust_anchor`, so the server
+/// cannot substitute the receiver of the transfer.
+pub fn create_transfer_to_alias(
+    sender: &SecretState,
+    amount: u64,
+    query: &AliasQuery,
+    proof: &AliasProof,
+    trust_anchor: &TrustAnchor,
+    rollback_delay: u32,
+) -> Result<Transfer, AliasTransferError> {
+    let checked = proof
+        .check(trust_anchor, query)
+        .map_err(AliasTransferError::Proof)?;
+    let receiver = checked.key.ok_or(AliasTransferError::UnknownAlias)?;
+    Ok(sender.create_transfer(amount, &receiver, rollback_delay))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_amounts() {
+        let decimals = Decimals::new(2);
+        assert_eq!(decimals.parse("123.45"), Ok(12_345));
+        assert_eq!(decimals.parse("123.4"), Ok(12_340));
+        assert_eq!(decimals.parse("123"), Ok(12_300));
+        assert_eq!(decimals.parse("0.01"), Ok(1));
+        assert_eq!(decimals.parse("007.00"), Ok(700));
+
+        let decimals = Decimals::new(0);
+        assert_eq!(decimals.parse("123"), Ok(123));
+        assert_eq!(
+            decimals.parse("123.0"),
+            Err(AmountError::ExcessPrecision { decimals: 0 })
+        );
+    }
+
+    #[test]
+    fn parsing_invalid_amounts() {
+        let decimals = Decimals::new(2);
+        assert_eq!(
+            decimals.parse("123.456"),
+            Err(AmountError::ExcessPrecision { decimals: 2 })
+        );
+        assert_eq!(decimals.parse(""), Err(AmountError::Empty));
+        assert_eq!(decimals.parse(".5"), Err(AmountError::Empty));
+        assert_eq!(decimals.parse("5."), Err(AmountError::Empty));
+        assert_eq!(decimals.parse("-5"), Err(AmountError::InvalidChar));
+        assert_eq!(decimals.parse("+5"), Err(AmountError::InvalidChar));
+        assert_eq!(decimals.parse(" 5"), Err(AmountError::InvalidChar));
+        assert_eq!(decimals.parse("1.2.3"), Err(AmountError::InvalidChar));
+        assert_eq!(decimals.parse("1.234x"), Err(AmountError::InvalidChar));
+        assert_eq!(decimals.parse("1e5"), Err(AmountError::InvalidChar));
+    }
+
+    #[test]
+    fn parsing_rejects_overflow() {
+        let decimals = Decimals::new(2);
+        assert_eq!(decimals.parse("184467440737095516.15"), Ok(u64::max_value()));
+        assert_eq!(
+            decimals.parse("184467440737095516.16"),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(
+            decimals.parse("184467440737095517"),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(
+            decimals.parse("100000000000000000000000"),
+            Err(AmountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn formatting_amounts() {
+        let decimals = Decimals::new(2);
+        assert_eq!(decimals.format(12_345), "123.45");
+        assert_eq!(decimals.format(150), "1.50");
+        assert_eq!(decimals.format(5), "0.05");
+        assert_eq!(decimals.format(0), "0.00");
+        assert_eq!(Decimals::new(0).format(123), "123");
+        assert_eq!(
+            Decimals::new(19).format(u64::max_value()),
+            "1.8446744073709551615"
+        );
+    }
+
+    #[test]
+    fn amounts_roundtrip() {
+        for &decimals in &[0, 2, 8, 19] {
+            let decimals = Decimals::new(decimals);
+            for &amount in &[0, 1, 99, 12_345, 1_000_000, u64::max_value()] {
+                let formatted = decimals.format(amount);
+                assert_eq!(decimals.parse(&formatted), Ok(amount));
+            }
+        }
+        let decimals = Decimals::new(2);
+        assert_eq!(decimals.format(decimals.parse("123.45").unwrap()), "123.45");
+    }
+}
@@ -16,7 +16,7 @@
 //! in the `sodiumoxide` crate.
 
 pub(crate) use sodiumoxide::crypto::box_::{
-    gen_nonce, open, open_precomputed, precompute, seal, Nonce,
+    gen_nonce, open, open_precomputed, precompute, seal, Nonce, MACBYTES, NONCEBYTES,
 };
 pub use sodiumoxide::crypto::box_::{PublicKey, SecretKey};
 
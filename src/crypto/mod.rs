@@ -36,4 +36,6 @@ pub mod enc;
 mod proofs;
 mod serialization;
 
-pub use self::proofs::{Commitment, Opening, SimpleRangeProof};
+pub use self::proofs::{
+    prove_in_range, Commitment, DecodeError, Opening, RangeDisclosureProof, SimpleRangeProof,
+};
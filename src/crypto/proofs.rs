@@ -21,7 +21,7 @@ use curve25519::{
     scalar::Scalar,
 };
 use merlin::Transcript;
-use rand::thread_rng;
+use rand::{thread_rng, CryptoRng, Rng};
 
 use std::ops;
 
@@ -32,6 +32,45 @@ lazy_static! {
     static ref BULLETPROOF_GENS: BulletproofGens = BulletproofGens::new(SimpleRangeProof::BITS, 1);
 }
 
+/// Error decoding a serialized [`Commitment`] or [`SimpleRangeProof`].
+///
+/// Indexes in variants refer to 32-byte elements of the serialized value.
+///
+/// [`Commitment`]: self::Commitment
+/// [`SimpleRangeProof`]: self::SimpleRangeProof
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Fail)]
+pub enum DecodeError {
+    /// The serialized value has unexpected byte length.
+    #[fail(display = "invalid byte length: {} expected, got {}", expected, actual)]
+    InvalidLength {
+        /// Expected byte length.
+        expected: usize,
+        /// Actual byte length.
+        actual: usize,
+    },
+
+    /// An element is not a canonically encoded compressed Ristretto point.
+    #[fail(
+        display = "element #{} is not a canonically encoded Ristretto point",
+        index
+    )]
+    InvalidPoint {
+        /// Zero-based index of the element.
+        index: usize,
+    },
+
+    /// An element is not a canonically encoded scalar.
+    #[fail(display = "element #{} is not a canonically encoded scalar", index)]
+    InvalidScalar {
+        /// Zero-based index of the element.
+        index: usize,
+    },
+
+    /// The value is rejected by the underlying implementation.
+    #[fail(display = "malformed value")]
+    Malformed,
+}
+
 /// Pedersen commitment to an integer value.
 ///
 /// # Theory
@@ -108,7 +147,16 @@ impl Commitment {
     ///
     /// Returns the created commitment and the corresponding opening for it.
     pub fn new(value: u64) -> (Self, Opening) {
-        let blinding = Scalar::random(&mut thread_rng());
+        Self::with_rng(value, &mut thread_rng())
+    }
+
+    /// Creates a commitment with the blinding chosen from the provided RNG.
+    ///
+    /// This is mostly useful for tests, which may supply a seeded RNG in order to get
+    /// reproducible commitments. The RNG must be cryptographically secure; outside of tests,
+    /// use [`new()`](#method.new), which relies on the thread-local RNG.
+    pub fn with_rng<R: Rng + CryptoRng>(value: u64, rng: &mut R) -> (Self, Opening) {
+        let blinding = Scalar::random(rng);
         let opening = Opening::new(value, blinding);
         (Self::from_opening(&opening), opening)
     }
@@ -129,14 +177,27 @@ impl Commitment {
 
     /// Attempts to deserialize a commitment from byte slice.
     pub fn from_slice(slice: &[u8]) -> Option<Self> {
+        Self::from_bytes(slice).ok()
+    }
+
+    /// Deserializes a commitment from byte slice, providing the reason of a failure
+    /// if the slice is not a valid encoding.
+    ///
+    /// The encoding is the canonical encoding of Ristretto points (i.e., compressed points
+    /// as per the Ristretto spec); there is no alternative uncompressed encoding.
+    pub fn from_bytes(slice: &[u8]) -> Result<Self, DecodeError> {
         if slice.len() != Self::BYTE_LEN {
-            return None;
+            return Err(DecodeError::InvalidLength {
+                expected: Self::BYTE_LEN,
+                actual: slice.len(),
+            });
         }
 
         let compressed_point = CompressedRistretto::from_slice(slice);
         compressed_point
             .decompress()
             .map(|point| Commitment { inner: point })
+            .ok_or(DecodeError::InvalidPoint { index: 0 })
     }
 
     /// Serializes this commitment to bytes.
@@ -237,7 +298,7 @@ pub struct Opening {
 
 impl Opening {
     /// Size of a serialized opening.
-    const BYTE_SIZE: usize = 40;
+    pub(crate) const BYTE_SIZE: usize = 40;
 
     pub(crate) fn new(value: u64, blinding: Scalar) -> Self {
         Opening { value, blinding }
@@ -405,9 +466,53 @@ impl SimpleRangeProof {
 
     /// Attempts to deserialize this proof from a byte slice.
     pub fn from_slice(slice: &[u8]) -> Option<Self> {
-        Some(SimpleRangeProof {
-            inner: RangeProof::from_bytes(slice).ok()?,
-        })
+        Self::from_bytes(slice).ok()
+    }
+
+    /// Checks if a 32-byte element of the serialized proof with the specified index
+    /// is a compressed Ristretto point (otherwise, it is a scalar).
+    ///
+    /// The layout of the serialized proof is as follows:
+    ///
+    /// - 4 points (`A`, `S`, `T_1`, `T_2`)
+    /// - 3 scalars (`t_x`, `t_x_blinding`, `e_blinding`)
+    /// - 12 points (6 pairs `L_i`, `R_i` from the inner product proof)
+    /// - 2 scalars (`a`, `b` from the inner product proof)
+    fn is_point_element(index: usize) -> bool {
+        index < 4 || (index >= 7 && index < Self::ELEMENTS_SIZE - 2)
+    }
+
+    /// Deserializes this proof from a byte slice, providing the reason of a failure
+    /// if the slice is not a valid encoding.
+    ///
+    /// Unlike the underlying implementation, this method checks that all points
+    /// in the proof are canonically encoded.
+    pub fn from_bytes(slice: &[u8]) -> Result<Self, DecodeError> {
+        let expected_len = Self::ELEMENTS_SIZE * 32;
+        if slice.len() != expected_len {
+            return Err(DecodeError::InvalidLength {
+                expected: expected_len,
+                actual: slice.len(),
+            });
+        }
+
+        for (index, element) in slice.chunks(32).enumerate() {
+            if Self::is_point_element(index) {
+                let point = CompressedRistretto::from_slice(element);
+                if point.decompress().is_none() {
+                    return Err(DecodeError::InvalidPoint { index });
+                }
+            } else {
+                let mut scalar_bytes = [0_u8; 32];
+                scalar_bytes.copy_from_slice(element);
+                if Scalar::from_canonical_bytes(scalar_bytes).is_none() {
+                    return Err(DecodeError::InvalidScalar { index });
+                }
+            }
+        }
+
+        let inner = RangeProof::from_bytes(slice).map_err(|_| DecodeError::Malformed)?;
+        Ok(SimpleRangeProof { inner })
     }
 
     /// Verifies this proof with respect to the given committed value.
@@ -456,8 +561,6 @@ fn commitments_produced_by_bulletproofs_are_as_expected() {
 
 #[test]
 fn range_proof_serialized_size_is_as_expected() {
-    use rand::Rng;
-
     let mut rng = thread_rng();
     for _ in 0..5 {
         let opening = Opening::new(rng.gen(), Scalar::random(&mut rng));
@@ -466,6 +569,39 @@ fn range_proof_serialized_size_is_as_expected() {
     }
 }
 
+#[test]
+fn non_canonical_proof_elements_are_rejected() {
+    let (_, opening) = Commitment::new(12345);
+    let bytes = SimpleRangeProof::prove(&opening).expect("prove").to_bytes();
+    assert!(SimpleRangeProof::from_bytes(&bytes).is_ok());
+
+    let mut invalid_point = bytes.clone();
+    // Encodings with the lowest bit set correspond to negative field elements,
+    // which are not allowed by the Ristretto spec.
+    invalid_point[32 * 8] |= 1;
+    assert_eq!(
+        SimpleRangeProof::from_bytes(&invalid_point).unwrap_err(),
+        DecodeError::InvalidPoint { index: 8 }
+    );
+
+    let mut invalid_scalar = bytes.clone();
+    for byte in &mut invalid_scalar[32 * 5..32 * 6] {
+        *byte = 0xff;
+    }
+    assert_eq!(
+        SimpleRangeProof::from_bytes(&invalid_scalar).unwrap_err(),
+        DecodeError::InvalidScalar { index: 5 }
+    );
+
+    assert_eq!(
+        SimpleRangeProof::from_bytes(&bytes[..32 * 20]).unwrap_err(),
+        DecodeError::InvalidLength {
+            expected: 32 * 21,
+            actual: 32 * 20,
+        }
+    );
+}
+
 #[test]
 fn incorrect_proofs_do_not_verify() {
     let (_, opening) = Commitment::new(12345);
@@ -473,3 +609,134 @@ fn incorrect_proofs_do_not_verify() {
     let (commitment2, _) = Commitment::new(54321);
     assert!(!proof.verify(&commitment2));
 }
+
+#[test]
+fn commitments_with_seeded_rng_are_reproducible() {
+    use rand::{prng::ChaChaRng, SeedableRng};
+
+    const SEED: [u8; 32] = [7; 32];
+
+    let (commitment, opening) = Commitment::with_rng(12345, &mut ChaChaRng::from_seed(SEED));
+    let (other_commitment, other_opening) =
+        Commitment::with_rng(12345, &mut ChaChaRng::from_seed(SEED));
+    assert_eq!(commitment, other_commitment);
+    assert_eq!(opening, other_opening);
+
+    let proof = SimpleRangeProof::prove(&opening).expect("prove");
+    assert!(proof.verify(&commitment));
+    let other_proof = SimpleRangeProof::prove(&other_opening).expect("prove");
+    assert!(other_proof.verify(&commitment));
+
+    let (different_commitment, _) =
+        Commitment::with_rng(12345, &mut ChaChaRng::from_seed([8; 32]));
+    assert_ne!(commitment, different_commitment);
+}
+
+/// Proof that a committed value lies in a publicly known range `[low, high]`
+/// (both bounds are inclusive).
+///
+/// The proof consists of two [`SimpleRangeProof`]s: for `value - low` and for `high - value`.
+/// Since both proofs assert that the value belongs to `[0, 1 << 64)`, the verifier learns
+/// nothing about the committed value besides it being within the range.
+///
+/// Use [`prove_in_range`] to create proofs.
+///
+/// # Examples
+///
+/// ```
+/// # use private_currency::crypto::{prove_in_range, Commitment};
+/// let (commitment, opening) = Commitment::new(3_000);
+/// let proof = prove_in_range(&commitment, &opening, 1_000, 5_000).unwrap();
+/// assert!(proof.verify(&commitment, 1_000, 5_000));
+/// assert!(!proof.verify(&commitment, 4_000, 5_000));
+/// ```
+///
+/// [`SimpleRangeProof`]: self::SimpleRangeProof
+/// [`prove_in_range`]: fn.prove_in_range.html
+#[derive(Debug, Clone)]
+pub struct RangeDisclosureProof {
+    lower: SimpleRangeProof,
+    upper: SimpleRangeProof,
+}
+
+impl RangeDisclosureProof {
+    /// Verifies this proof with respect to the given committed value and range bounds.
+    pub fn verify(&self, commitment: &Commitment, low: u64, high: u64) -> bool {
+        low <= high
+            && self
+                .lower
+                .verify(&(commitment - &Commitment::with_no_blinding(low)))
+            && self
+                .upper
+                .verify(&(&Commitment::with_no_blinding(high) - commitment))
+    }
+}
+
+/// Proves that the value in the `commitment` lies in the range `[low, high]`.
+///
+/// # Return value
+///
+/// Returns `None` if the `opening` does not correspond to the `commitment`, the committed value
+/// is outside of the range, or any of the underlying range proofs cannot be created.
+pub fn prove_in_range(
+    commitment: &Commitment,
+    opening: &Opening,
+    low: u64,
+    high: u64,
+) -> Option<RangeDisclosureProof> {
+    if !commitment.verify(opening) || opening.value < low || opening.value > high {
+        return None;
+    }
+    let lower = SimpleRangeProof::prove(&(opening - &Opening::with_no_blinding(low)))?;
+    let upper = SimpleRangeProof::prove(&(&Opening::with_no_blinding(high) - opening))?;
+    Some(RangeDisclosureProof { lower, upper })
+}
+
+#[test]
+fn range_disclosure_proofs() {
+    let (commitment, opening) = Commitment::new(3_000);
+    let proof = prove_in_range(&commitment, &opening, 1_000, 5_000).expect("prove");
+    assert!(proof.verify(&commitment, 1_000, 5_000));
+    // The proof is not valid for other bounds or commitments.
+    assert!(!proof.verify(&commitment, 1_000, 4_999));
+    assert!(!proof.verify(&commitment, 1_001, 5_000));
+    assert!(!proof.verify(&commitment, 5_000, 1_000));
+    let (other_commitment, _) = Commitment::new(3_000);
+    assert!(!proof.verify(&other_commitment, 1_000, 5_000));
+}
+
+#[test]
+fn range_disclosure_proofs_at_boundaries() {
+    for &value in &[1_000, 5_000] {
+        let (commitment, opening) = Commitment::new(value);
+        let proof = prove_in_range(&commitment, &opening, 1_000, 5_000).expect("prove");
+        assert!(proof.verify(&commitment, 1_000, 5_000));
+    }
+
+    let (commitment, opening) = Commitment::new(1_000);
+    let proof = prove_in_range(&commitment, &opening, 1_000, 1_000).expect("prove");
+    assert!(proof.verify(&commitment, 1_000, 1_000));
+
+    let (commitment, opening) = Commitment::new(u64::max_value());
+    let proof = prove_in_range(&commitment, &opening, 0, u64::max_value()).expect("prove");
+    assert!(proof.verify(&commitment, 0, u64::max_value()));
+}
+
+#[test]
+fn range_disclosure_proofs_outside_of_range() {
+    for &value in &[0, 999, 5_001, u64::max_value()] {
+        let (commitment, opening) = Commitment::new(value);
+        assert!(prove_in_range(&commitment, &opening, 1_000, 5_000).is_none());
+
+        // A proof for another value does not verify for the commitment.
+        let (in_range_commitment, in_range_opening) = Commitment::new(3_000);
+        let proof =
+            prove_in_range(&in_range_commitment, &in_range_opening, 1_000, 5_000).expect("prove");
+        assert!(!proof.verify(&commitment, 1_000, 5_000));
+    }
+
+    // The opening must correspond to the commitment.
+    let (commitment, _) = Commitment::new(3_000);
+    let (_, other_opening) = Commitment::new(3_000);
+    assert!(prove_in_range(&commitment, &other_opening, 1_000, 5_000).is_none());
+}
@@ -13,6 +13,25 @@
 // limitations under the License.
 
 //! Supporting routines for serializing crypto types.
+//!
+//! # Encoding
+//!
+//! All group elements are encoded as compressed Ristretto points (32 bytes) according to
+//! [the Ristretto spec]. This is the only canonical encoding of Ristretto points, so there
+//! is no option to use uncompressed points; clients in other languages should use
+//! a Ristretto implementation rather than encoding the underlying Edwards points. Scalars
+//! are encoded as 32-byte little-endian integers reduced modulo the group order.
+//!
+//! | Type | Binary encoding | JSON encoding |
+//! |------|-----------------|---------------|
+//! | `Commitment` | 1 point | hex string |
+//! | `SimpleRangeProof` | 21 elements (points and scalars) | array of 21 hex strings |
+//!
+//! See [`SimpleRangeProof::from_bytes()`] for the order of elements in range proofs.
+//! Non-canonical encodings of points and scalars are rejected during deserialization.
+//!
+//! [the Ristretto spec]: https://ristretto.group/details/elligator_in_extended.html
+//! [`SimpleRangeProof::from_bytes()`]: ::crypto::SimpleRangeProof::from_bytes()
 
 use exonum::encoding::{
     serialize::{
@@ -55,9 +74,9 @@ impl<'a> Field<'a> for Commitment {
         let to = to.unchecked_offset() as usize;
 
         debug_assert_eq!((to - from) as u32, Self::field_size());
-        Commitment::from_slice(&buffer[from..to])
+        Commitment::from_bytes(&buffer[from..to])
             .map(|_| latest_segment)
-            .ok_or_else(|| "non-canonical `Commitment`".into())
+            .map_err(|e| Cow::from(format!("invalid `Commitment`: {}", e)).into())
     }
 }
 
@@ -83,10 +102,7 @@ impl FromHex for Commitment {
 
     fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
         let bytes = serialize::decode_hex(hex).map_err(|e| e.to_string())?;
-        if bytes.len() != Self::BYTE_LEN {
-            Err("invalid hex string length")?;
-        }
-        Commitment::from_slice(&bytes).ok_or_else(|| "non-canonical `Commitment`".to_owned())
+        Commitment::from_bytes(&bytes).map_err(|e| format!("invalid `Commitment`: {}", e))
     }
 }
 
@@ -130,6 +146,54 @@ fn commitment_roundtrip() {
     assert_eq!(value, value_copy);
 }
 
+#[test]
+fn commitment_golden_vectors() {
+    // Encodings of small multiples of the Ristretto basepoint from the test vectors
+    // in the Ristretto spec. As `G` in Pedersen commitments is the basepoint,
+    // `Comm(x; 0) = xG` are exactly these points.
+    const VECTORS: &[&str] = &[
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "e2f2ae0a6abc4e71a884a961c500515f58e30b6aa582dd8db6a65945e08d2d76",
+        "6a493210f7499cd17fecb510ae0cea23a110e8d5b901f8acadd3095c73a3b919",
+        "94741f5d5d52755ece4f23f044ee27d5d1ea1e2bd196b462166b16152a9d0259",
+        "da80862773358b466ffadfe0b3293ab3d9fd53c5ea6c955358f568322daf6a57",
+    ];
+
+    for (value, &hex) in VECTORS.iter().enumerate() {
+        let commitment = Commitment::with_no_blinding(value as u64);
+        assert_eq!(serialize::encode_hex(&commitment.to_bytes()), hex);
+        assert_eq!(Commitment::from_hex(hex).expect("from_hex"), commitment);
+        assert_eq!(
+            commitment.serialize_field().expect("serialize_field"),
+            Value::String(hex.to_owned())
+        );
+    }
+}
+
+#[test]
+fn non_canonical_commitments_are_rejected() {
+    use super::proofs::DecodeError;
+
+    // Bad encodings from the Ristretto spec: a negative field element
+    // and a non-canonical field element (`p`).
+    const BAD_ENCODINGS: &[&str] = &[
+        "0100000000000000000000000000000000000000000000000000000000000000",
+        "edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+    ];
+    for &hex in BAD_ENCODINGS {
+        let bytes = serialize::decode_hex(hex).expect("decode_hex");
+        assert_eq!(
+            Commitment::from_bytes(&bytes).unwrap_err(),
+            DecodeError::InvalidPoint { index: 0 }
+        );
+        let err = Commitment::from_hex(hex).unwrap_err();
+        assert!(err.contains("not a canonically encoded Ristretto point"));
+    }
+
+    let err = Commitment::from_hex("e2f2ae0a").unwrap_err();
+    assert!(err.contains("invalid byte length"));
+}
+
 impl<'a> SegmentField<'a> for SimpleRangeProof {
     fn item_size() -> u32 {
         32
@@ -167,9 +231,9 @@ impl<'a> SegmentField<'a> for SimpleRangeProof {
             Err("undersized buffer for `SimpleRangeProof`")?;
         }
 
-        SimpleRangeProof::from_slice(slice)
+        SimpleRangeProof::from_bytes(slice)
             .map(|_| latest_segment)
-            .ok_or_else(|| "incorrect `SimpleRangeProof`".into())
+            .map_err(|e| Cow::from(format!("invalid `SimpleRangeProof`: {}", e)).into())
     }
 }
 
@@ -196,7 +260,8 @@ impl ExonumJson for SimpleRangeProof {
         }
         debug_assert_eq!(bytes.len(), 32 * Self::ELEMENTS_SIZE as usize);
 
-        let proof = SimpleRangeProof::from_slice(&bytes).ok_or("invalid `SimpleRangeProof`")?;
+        let proof = SimpleRangeProof::from_bytes(&bytes)
+            .map_err(|e| format!("invalid `SimpleRangeProof`: {}", e))?;
         buffer.write(from, to, proof);
         Ok(())
     }
@@ -237,3 +302,35 @@ fn proof_roundtrip() {
     let value_copy = Value::from_bytes(value_bytes.into());
     assert_eq!(value, value_copy);
 }
+
+#[test]
+fn wrongly_encoded_proof_is_rejected() {
+    use super::proofs::Opening;
+    use exonum::encoding::serialize::json::reexport as serde_json;
+
+    encoding_struct! {
+        struct Wrapper {
+            proof: SimpleRangeProof,
+        }
+    }
+
+    let opening = Opening::with_no_blinding(12345);
+    let proof = SimpleRangeProof::prove(&opening).expect("prove");
+    let mut json = serde_json::to_value(&Wrapper::new(proof)).expect("to_value");
+    // Reverse a point in the proof, simulating a client using big-endian encoding.
+    let element = json["proof"][0].as_str().expect("hex string").to_owned();
+    let mut element_bytes = serialize::decode_hex(&element).expect("decode_hex");
+    element_bytes.reverse();
+    element_bytes[0] |= 1; // ensure that the encoding is invalid
+    json["proof"][0] = Value::String(serialize::encode_hex(&element_bytes));
+
+    let mut bytes = vec![0_u8; 8];
+    let err = SimpleRangeProof::deserialize_field(&json["proof"], &mut bytes, 0, 8)
+        .unwrap_err()
+        .to_string();
+    assert_eq!(
+        err,
+        "invalid `SimpleRangeProof`: element #0 is not a canonically encoded Ristretto point"
+    );
+    assert!(serde_json::from_value::<Wrapper>(json).is_err());
+}
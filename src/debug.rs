@@ -15,19 +15,27 @@
 //! Debugger for the service.
 
 use exonum::{
-    blockchain::{Schema as CoreSchema, ServiceContext},
-    crypto::Hash,
-    helpers::Height,
-    storage::{Fork, KeySetIndex, Snapshot},
+    blockchain::{
+        Block, Blockchain, Schema as CoreSchema, ServiceContext, Transaction, TransactionError,
+        TransactionSet, TxLocation,
+    },
+    crypto::{Hash, PublicKey},
+    helpers::{Height, ValidatorId},
+    messages::Message,
+    storage::{Error as StorageError, Fork, KeySetIndex, MapIndex, Snapshot},
 };
 
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    mpsc,
+use std::{
+    iter, panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
 };
 
 use storage::{maybe_transfer, EventTag, Schema};
-use transactions::Transfer;
+use transactions::{ConfigurableTransaction, CryptoTransactions, Transfer};
+use {Config, SERVICE_ID};
 
 /// Name of table containing transfers rolled back at the previous height.
 ///
@@ -45,6 +53,121 @@ pub enum DebugEvent {
         /// Height at which the rollback occurred.
         height: Height,
     },
+
+    /// An unaccepted transfer will be rolled back soon. Sent only if the
+    /// [`expiry_notice_blocks`] option is set.
+    ///
+    /// [`expiry_notice_blocks`]: struct.DebuggerOptions.html#structfield.expiry_notice_blocks
+    ExpiresSoon {
+        /// Hash of the transfer.
+        transfer_id: Hash,
+        /// Receiver of the transfer.
+        receiver: PublicKey,
+        /// Number of blocks, starting from the next one, in which the transfer can still
+        /// be accepted.
+        remaining_blocks: u32,
+        /// Height of the block after which the notification is sent.
+        height: Height,
+    },
+
+    /// A block has been committed. Sent only if [`export_blocks`] option is set.
+    ///
+    /// [`export_blocks`]: struct.DebuggerOptions.html#structfield.export_blocks
+    BlockCommitted(ExportedBlock),
+}
+
+/// Service transactions in a committed block, together with the block header.
+///
+/// Exported blocks form a log independent of the node storage. Replaying the transactions
+/// from the log block by block on a fresh blockchain with the same genesis configuration
+/// and services must reproduce the state hash recorded in the header of each block;
+/// this is checked by [`replay_blocks`].
+///
+/// Blocks can be exported with the debugger (see [`DebuggerOptions::export_blocks`]),
+/// or without it via [`ExportedBlock::at_height()`] and the `v1/debug/block` endpoint
+/// of the private API, or via [`ExportedBlock::latest()`] and the `v1/debug/latest-block`
+/// endpoint.
+///
+/// [`replay_blocks`]: fn.replay_blocks.html
+/// [`DebuggerOptions::export_blocks`]: struct.DebuggerOptions.html#structfield.export_blocks
+/// [`ExportedBlock::at_height()`]: #method.at_height
+/// [`ExportedBlock::latest()`]: #method.latest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedBlock {
+    /// Header of the block.
+    pub header: Block,
+    /// Service transactions in the block, in the order of their execution. This includes
+    /// transactions which have failed during execution.
+    pub transactions: Vec<CryptoTransactions>,
+    /// Service state hash after the block, as returned by [`Schema::state_hash()`].
+    /// Only recorded if the block was the latest one when exported, since the storage
+    /// does not retain the service state hash for earlier blocks.
+    ///
+    /// [`Schema::state_hash()`]: ::storage::Schema::state_hash()
+    pub state_hash: Option<Vec<Hash>>,
+}
+
+impl ExportedBlock {
+    /// Exports the committed block at the specified height from the snapshot.
+    /// Returns `None` if there is no such block.
+    ///
+    /// The service state hash is recorded only if `height` is the latest height.
+    pub fn at_height(snapshot: &dyn Snapshot, height: Height) -> Option<Self> {
+        let core_schema = CoreSchema::new(snapshot);
+        let header = core_schema
+            .block_hash_by_height(height)
+            .map(|hash| core_schema.blocks().get(&hash).expect("block header"))?;
+        let all_transactions = core_schema.transactions();
+        let transactions = core_schema
+            .block_transactions(height)
+            .iter()
+            .map(|hash| all_transactions.get(&hash).expect("transaction"))
+            .filter(|raw| raw.service_id() == SERVICE_ID)
+            .map(|raw| CryptoTransactions::tx_from_raw(raw).expect("service transaction"))
+            .collect();
+        let state_hash = if height == core_schema.height() {
+            Some(Schema::new(snapshot).state_hash())
+        } else {
+            None
+        };
+
+        Some(ExportedBlock {
+            header,
+            transactions,
+            state_hash,
+        })
+    }
+
+    /// Exports the latest committed block from the snapshot.
+    pub fn latest(snapshot: &dyn Snapshot) -> Self {
+        let height = CoreSchema::new(snapshot).height();
+        Self::at_height(snapshot, height).expect("latest block")
+    }
+
+    /// Returns the height of the block.
+    pub fn height(&self) -> Height {
+        self.header.height()
+    }
+}
+
+// Transactions are compared by their hashes, since `CryptoTransactions` does not implement
+// `PartialEq`.
+impl PartialEq for ExportedBlock {
+    fn eq(&self, other: &Self) -> bool {
+        let hashes = |block: &Self| -> Vec<Hash> {
+            block
+                .transactions
+                .iter()
+                .map(|tx| {
+                    let tx: Box<dyn Transaction> = tx.clone().into();
+                    tx.hash()
+                })
+                .collect()
+        };
+        self.header == other.header
+            && self.state_hash == other.state_hash
+            && hashes(self) == hashes(other)
+    }
 }
 
 /// Debugger provides ability to connect to the service and retrieve information
@@ -82,6 +205,26 @@ pub struct DebuggerOptions {
     /// This is an expensive operation; it is *at least* linear w.r.t. the number of
     /// wallets in the system.
     pub check_invariants: bool,
+
+    /// Send a [`BlockCommitted`] event with the block transactions and the resulting
+    /// state hash on each `after_commit`. The events may be written to an external log
+    /// and used to rebuild the service state from scratch, e.g., for disaster recovery.
+    ///
+    /// [`BlockCommitted`]: enum.DebugEvent.html#variant.BlockCommitted
+    pub export_blocks: bool,
+
+    /// Send an [`ExpiresSoon`] event for each unaccepted transfer when the specified number
+    /// of blocks remains before the transfer is rolled back. The events may be forwarded
+    /// to receivers, so that they can accept transfers in time. Zero disables the events.
+    ///
+    /// Transfers with a rollback delay shorter than this lead time are notified after
+    /// the block including them, with the actual number of remaining blocks.
+    ///
+    /// The same information is available without the debugger via the
+    /// `v1/wallet/pending-transfers` endpoint of the public API.
+    ///
+    /// [`ExpiresSoon`]: enum.DebugEvent.html#variant.ExpiresSoon
+    pub expiry_notice_blocks: u32,
 }
 
 impl Iterator for Debugger {
@@ -92,6 +235,270 @@ impl Iterator for Debugger {
     }
 }
 
+/// Result of re-verifying a single transaction with [`verify_block`].
+///
+/// [`verify_block`]: fn.verify_block.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionCheck {
+    /// The transaction is valid and has been executed successfully.
+    Executed,
+    /// The transaction is valid, but its execution has failed. Such transactions
+    /// may be legitimately included into a block.
+    Failed(TransactionError),
+    /// The transaction is valid, but its execution has panicked. Contains the panic message
+    /// if it is a string. Like failed transactions, such transactions may be included
+    /// into a block.
+    Panicked(Option<String>),
+    /// The transaction does not pass stateless verification (e.g., its signature
+    /// or a range proof is invalid). Such transactions cannot be included into a valid block,
+    /// so they are not executed.
+    Invalid,
+}
+
+/// Report produced by [`verify_block`].
+///
+/// [`verify_block`]: fn.verify_block.html
+#[derive(Debug)]
+pub struct BlockVerificationReport {
+    /// Height of the verified block.
+    pub height: Height,
+    /// Results of checking the block transactions, in the order of their execution.
+    pub transactions: Vec<(Hash, TransactionCheck)>,
+    /// Service state hash after re-executing the block.
+    pub state_hash: Vec<Hash>,
+    /// Whether `state_hash` coincides with the service state hash recorded in the block,
+    /// or `None` if the block does not record it (see [`ExportedBlock::state_hash`]).
+    /// In the latter case, the state hash can be checked by replaying the block
+    /// with [`replay_blocks`].
+    ///
+    /// [`ExportedBlock::state_hash`]: struct.ExportedBlock.html#structfield.state_hash
+    /// [`replay_blocks`]: fn.replay_blocks.html
+    pub state_hash_matches: Option<bool>,
+}
+
+impl BlockVerificationReport {
+    /// Checks if the block is valid, i.e., all its transactions pass stateless verification,
+    /// and re-executing them leads to the recorded service state hash if the block records it.
+    pub fn is_ok(&self) -> bool {
+        self.state_hash_matches != Some(false)
+            && self
+                .transactions
+                .iter()
+                .all(|(_, check)| *check != TransactionCheck::Invalid)
+    }
+}
+
+/// Re-verifies an exported block by re-running stateless verification and then executing
+/// every transaction of the block on top of `fork`.
+///
+/// `fork` must contain the pre-block state, i.e., its latest block must precede `block`;
+/// e.g., it may be obtained with `Blockchain::fork()` after replaying earlier exported
+/// blocks on a fresh node with [`replay_blocks`]. All changes are made to the fork,
+/// which is dropped afterwards. Transactions are verified and executed with `config`,
+/// which should be the configuration of the service that has produced the block.
+///
+/// # Errors
+///
+/// Returns an error if the height of `fork` does not precede the height of `block`.
+///
+/// [`replay_blocks`]: fn.replay_blocks.html
+pub fn verify_block(
+    mut fork: Fork,
+    block: &ExportedBlock,
+    config: &Config,
+) -> Result<BlockVerificationReport, ReplayError> {
+    let height = CoreSchema::new(&fork).height().next();
+    if height != block.height() {
+        return Err(ReplayError::HeightMismatch {
+            expected: height,
+            actual: block.height(),
+        });
+    }
+
+    let mut transactions = Vec::with_capacity(block.transactions.len());
+    let mut executed_count = 0;
+    for tx in &block.transactions {
+        let tx = tx.clone().into_configurable();
+        let hash = tx.hash();
+        if !tx.verify_with_config(config) {
+            transactions.push((hash, TransactionCheck::Invalid));
+            continue;
+        }
+
+        let check = execute_transaction(tx.as_ref(), &mut fork, config);
+        // Record the transaction in the same way the core does, so that the following
+        // transactions in the block can refer to it.
+        CoreSchema::new(&mut fork).add_transaction_into_pool(tx.raw().clone());
+        record_transaction_location(&mut fork, &hash, TxLocation::new(height, executed_count));
+        executed_count += 1;
+        transactions.push((hash, check));
+    }
+    // Mirrors `Service::before_commit()`, which is not invoked for the genesis block.
+    if height > Height(0) {
+        Schema::new(&mut fork).do_rollback();
+    }
+
+    let state_hash = Schema::new(&fork).state_hash();
+    Ok(BlockVerificationReport {
+        height,
+        transactions,
+        state_hash_matches: block
+            .state_hash
+            .as_ref()
+            .map(|expected| *expected == state_hash),
+        state_hash,
+    })
+}
+
+/// Records the location of a committed transaction, which the core does on block commit.
+///
+/// The core schema does not expose a public method to write transaction locations,
+/// so this is the only place in the service writing to a core table directly.
+/// The table name must match `Schema::transactions_locations()` in `exonum::blockchain`.
+fn record_transaction_location(fork: &mut Fork, hash: &Hash, location: TxLocation) {
+    MapIndex::new("core.transactions_locations", fork).put(hash, location);
+}
+
+/// Executes a transaction in the same way as the core does during block creation.
+fn execute_transaction(
+    tx: &dyn ConfigurableTransaction,
+    fork: &mut Fork,
+    config: &Config,
+) -> TransactionCheck {
+    fork.checkpoint();
+    match panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        tx.execute_with_config(fork, config)
+    })) {
+        Ok(Ok(())) => {
+            fork.commit();
+            TransactionCheck::Executed
+        }
+        Ok(Err(e)) => {
+            fork.rollback();
+            TransactionCheck::Failed(e.into())
+        }
+        Err(err) => {
+            if err.is::<StorageError>() {
+                panic::resume_unwind(err);
+            }
+            fork.rollback();
+            let message = err
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| err.downcast_ref::<String>().cloned());
+            TransactionCheck::Panicked(message)
+        }
+    }
+}
+
+/// Error occurring when replaying exported blocks with [`replay_blocks`]
+/// or verifying them with [`verify_block`].
+///
+/// [`replay_blocks`]: fn.replay_blocks.html
+/// [`verify_block`]: fn.verify_block.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+pub enum ReplayError {
+    /// The height of an exported block does not follow the height of the blockchain.
+    #[fail(
+        display = "exported block at height {} cannot be applied at height {}",
+        actual,
+        expected
+    )]
+    HeightMismatch {
+        /// Height of the next block in the blockchain.
+        expected: Height,
+        /// Height of the exported block.
+        actual: Height,
+    },
+
+    /// A transaction in an exported block does not pass stateless verification.
+    #[fail(
+        display = "transaction {:?} in block at height {} is invalid",
+        transaction,
+        height
+    )]
+    InvalidTransaction {
+        /// Height of the block.
+        height: Height,
+        /// Hash of the transaction.
+        transaction: Hash,
+    },
+
+    /// The blockchain state hash after replaying a block differs from the one recorded
+    /// in the header of the exported block.
+    #[fail(display = "state hash mismatch after block at height {}", height)]
+    StateHashMismatch {
+        /// Height of the block.
+        height: Height,
+    },
+}
+
+/// Rebuilds the service state by committing exported blocks on top of `blockchain`.
+/// After each block, the blockchain state hash is compared to the one recorded
+/// in the header of the exported block.
+///
+/// `blockchain` must include the service and its latest block must precede the first
+/// exported block; e.g., it may be a fresh blockchain if the blocks are exported starting
+/// from height 1. For the state hashes to match, `blockchain` must be initialized
+/// with the same genesis configuration and services (including the service configuration)
+/// as the blockchain the blocks were exported from, and the blocks must not contain
+/// transactions of other services. Transactions are verified with the configuration
+/// of the service in `blockchain`. Replaying stops on the first error; blocks committed
+/// before the error remain in the blockchain.
+///
+/// # Panics
+///
+/// Panics if `blockchain` does not include the service, or if committing a block
+/// to the blockchain storage fails.
+pub fn replay_blocks<'a, I>(blockchain: &mut Blockchain, blocks: I) -> Result<(), ReplayError>
+where
+    I: IntoIterator<Item = &'a ExportedBlock>,
+{
+    for block in blocks {
+        let height = blockchain.last_block().height().next();
+        if height != block.height() {
+            return Err(ReplayError::HeightMismatch {
+                expected: height,
+                actual: block.height(),
+            });
+        }
+
+        let mut fork = blockchain.fork();
+        let mut hashes = Vec::with_capacity(block.transactions.len());
+        {
+            let mut core_schema = CoreSchema::new(&mut fork);
+            for tx in &block.transactions {
+                let tx: Box<dyn Transaction> = tx.clone().into();
+                let tx = blockchain
+                    .tx_from_raw(tx.raw().clone())
+                    .expect("service is not included into the blockchain");
+                let hash = tx.hash();
+                if !tx.verify() {
+                    return Err(ReplayError::InvalidTransaction {
+                        height,
+                        transaction: hash,
+                    });
+                }
+                core_schema.add_transaction_into_pool(tx.raw().clone());
+                hashes.push(hash);
+            }
+        }
+        blockchain
+            .merge(fork.into_patch())
+            .expect("cannot add transactions to pool");
+
+        let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), height, &hashes);
+        blockchain
+            .commit(&patch, block_hash, iter::empty())
+            .expect("cannot commit block");
+
+        if blockchain.last_block().state_hash() != block.header.state_hash() {
+            return Err(ReplayError::StateHashMismatch { height });
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub(crate) struct DebuggerProbe {
     tx: mpsc::SyncSender<DebugEvent>,
@@ -128,7 +535,7 @@ impl DebuggerProbe {
         schema.copy_rolled_back_transfers();
     }
 
-    pub fn on_after_commit(&self, context: &ServiceContext) {
+    pub fn on_after_commit(&self, context: &ServiceContext, config: &Config) {
         if self.is_shutdown() {
             return;
         }
@@ -137,7 +544,15 @@ impl DebuggerProbe {
         let schema = Schema::new(&snapshot);
 
         if self.options.check_invariants {
-            schema.check_invariants();
+            schema.check_invariants(config);
+        }
+
+        if self.options.export_blocks {
+            let block = ExportedBlock::latest(snapshot);
+            if self.tx.send(DebugEvent::BlockCommitted(block)).is_err() {
+                self.shutdown();
+                return;
+            }
         }
 
         // Send rolled back transfers to the debugger.
@@ -151,6 +566,57 @@ impl DebuggerProbe {
         if result.is_err() {
             // The debugger is shut down, we can shut down operations as well.
             self.shutdown();
+            return;
+        }
+
+        // Notify about transfers that will be rolled back after `expiry_notice_blocks` blocks.
+        // Transfers scheduled for rollback at height `h` are rolled back in `before_commit`
+        // of the block at height `h + 1`, so they can be accepted up until that block.
+        let notice_blocks = self.options.expiry_notice_blocks;
+        if notice_blocks > 0 {
+            let rollback_height = Height(height.0 + u64::from(notice_blocks) - 1);
+            let mut expiring: Vec<_> = schema
+                .rollback_transfers(rollback_height)
+                .into_iter()
+                .map(|transfer_id| (transfer_id, notice_blocks))
+                .collect();
+            // Transfers with a short rollback delay never reach the notice point, so they
+            // are notified in the block including them.
+            expiring.extend(
+                CoreSchema::new(&snapshot)
+                    .block_transactions(height)
+                    .iter()
+                    .filter_map(|transfer_id| {
+                        let transfer = maybe_transfer(&snapshot, &transfer_id)?;
+                        let scheduled_height = schema.scheduled_rollback_height(&transfer_id)?;
+                        let is_pending = schema
+                            .unaccepted_transfers_index(transfer.to())
+                            .contains(&transfer_id);
+                        if is_pending && scheduled_height < rollback_height {
+                            let remaining_blocks = scheduled_height.0 + 1 - height.0;
+                            Some((transfer_id, remaining_blocks as u32))
+                        } else {
+                            None
+                        }
+                    }),
+            );
+
+            let result: Result<(), _> = expiring
+                .into_iter()
+                .map(|(transfer_id, remaining_blocks)| {
+                    let transfer = maybe_transfer(&snapshot, &transfer_id).expect("Transfer");
+                    DebugEvent::ExpiresSoon {
+                        transfer_id,
+                        receiver: *transfer.to(),
+                        remaining_blocks,
+                        height,
+                    }
+                })
+                .map(|message| self.tx.send(message).map_err(drop))
+                .collect();
+            if result.is_err() {
+                self.shutdown();
+            }
         }
     }
 }
@@ -160,7 +626,7 @@ impl<T: AsRef<dyn Snapshot>> Schema<T> {
         KeySetIndex::new(ROLLED_BACK_TRANSFERS, &self.inner)
     }
 
-    fn check_invariants(&self) {
+    fn check_invariants(&self, config: &Config) {
         let wallets = self.wallets();
         for wallet in wallets.values() {
             let pk = wallet.public_key();
@@ -196,6 +662,20 @@ impl<T: AsRef<dyn Snapshot>> Schema<T> {
                 }
             }
         }
+
+        // Check that pre-accepted transfers are still awaiting acceptance.
+        for transfer_id in self.pre_accepted_transfers().keys() {
+            let transfer = maybe_transfer(&self.inner, &transfer_id).expect("Transfer");
+            assert!(
+                self.unaccepted_transfers_index(transfer.to())
+                    .contains(&transfer_id),
+                "pre-accepted transfer is not in unaccepted transfers"
+            );
+        }
+
+        if let Err(e) = self.verify_rollback_queue_integrity(config) {
+            panic!("{}", e);
+        }
     }
 }
 
@@ -31,6 +31,8 @@ extern crate curve25519_dalek as curve25519;
 extern crate exonum_sodiumoxide as sodiumoxide;
 extern crate failure;
 extern crate merlin;
+#[cfg(feature = "testing")]
+extern crate proptest;
 extern crate rand;
 #[macro_use]
 extern crate failure_derive;
@@ -39,7 +41,7 @@ extern crate serde;
 extern crate serde_derive;
 
 use exonum::{
-    api::ServiceApiBuilder,
+    api::{ServiceApiBuilder, ServiceApiState},
     blockchain::{self as bc, ServiceContext, Transaction},
     crypto::Hash,
     encoding::Error as EncodingError,
@@ -47,22 +49,30 @@ use exonum::{
     storage::{Fork, Snapshot},
 };
 
-use std::ops::Range;
+use std::{ops::Range, sync::Arc};
 
 pub mod api;
+pub mod client;
 pub mod crypto;
 mod debug;
 mod secrets;
 pub mod storage;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod transactions;
 mod utils;
 
 pub use api::Api;
+use api::TransferQuery;
 use debug::DebuggerProbe;
-pub use debug::{DebugEvent, Debugger, DebuggerOptions};
-pub use secrets::{EncryptedData, SecretState, VerifiedTransfer};
+pub use debug::{
+    replay_blocks, verify_block, BlockVerificationReport, DebugEvent, Debugger, DebuggerOptions,
+    ExportedBlock, ReplayError, TransactionCheck,
+};
+pub use secrets::{EncryptedData, FundsAttestation, SecretState, VerifiedTransfer};
 pub use storage::{Schema, Wallet};
 pub use transactions::CryptoTransactions as Transactions;
+use transactions::{ActivationHeights, ConfiguredTransaction, EnabledTransactions};
 
 /// Human-readable service name.
 pub const SERVICE_NAME: &str = "private_currency";
@@ -72,7 +82,15 @@ pub const SERVICE_ID: u16 = 2_000;
 pub const CONFIG: Config = Config {
     initial_balance: 1_000_000,
     rollback_delay_bounds: 5..1_000,
+    default_rollback_delay: 10,
     min_transfer_amount: 1,
+    finality_depth: 6,
+    enabled_transactions: EnabledTransactions::ALL,
+    auto_create_receivers: false,
+    max_custodial_accepts: 100,
+    encrypted_data_dedup_window: 0,
+    activation_heights: ActivationHeights::GENESIS,
+    max_receipt_len: 256,
 };
 
 /// Service configuration.
@@ -82,26 +100,157 @@ pub struct Config {
     pub initial_balance: u64,
     /// Acceptable bounds on the `Transfer::rollback_delay()` parameter.
     pub rollback_delay_bounds: Range<u32>,
+    /// Recommended value of the `Transfer::rollback_delay()` parameter for clients
+    /// that do not have specific requirements. Must lie within `rollback_delay_bounds`.
+    pub default_rollback_delay: u32,
     /// Minimum acceptable transfer amount.
     pub min_transfer_amount: u64,
+    /// Number of confirmations (i.e., blocks committed on top of the block with a transfer)
+    /// after which clients may treat a settled transfer as final.
+    ///
+    /// See [`TransferStatus`](::api::TransferStatus) for details.
+    pub finality_depth: u64,
+    /// Transaction types enabled in the deployment.
+    pub enabled_transactions: EnabledTransactions,
+    /// Whether a `Transfer` to an unregistered key creates a wallet for the receiver
+    /// instead of failing with [`UnregisteredReceiver`].
+    ///
+    /// The implicitly created wallet is initialized in the same way as with `CreateWallet`.
+    /// Since the receiver key is not authenticated by a signature in this case, it must
+    /// be a valid Ed25519 point, so that the encryption key for the wallet can be derived
+    /// from it as usual.
+    ///
+    /// [`UnregisteredReceiver`]: ::transactions::Error::UnregisteredReceiver
+    pub auto_create_receivers: bool,
+    /// Maximum number of `Accept` transactions in a single
+    /// [`CustodialAcceptMany`](::transactions::CustodialAcceptMany).
+    pub max_custodial_accepts: u32,
+    /// Number of the latest entries in the sender’s wallet history checked for outgoing
+    /// transfers with the same `encrypted_data` as a new `Transfer`. If such a transfer
+    /// is found, the new transfer fails with [`DuplicateEncryptedData`]. Zero disables the check.
+    ///
+    /// Byte-identical encrypted data may indicate that the transfer was created by copying
+    /// parts of a prior transfer, since honest clients use a fresh nonce for each transfer.
+    ///
+    /// [`DuplicateEncryptedData`]: ::transactions::Error::DuplicateEncryptedData
+    pub encrypted_data_dedup_window: u64,
+    /// Heights from which transaction types are active.
+    pub activation_heights: ActivationHeights,
+    /// Maximum length in bytes of a receipt attached (before encryption) to
+    /// an [`AcceptWithReceipt`](::transactions::AcceptWithReceipt) transaction.
+    pub max_receipt_len: u32,
+}
+
+impl Config {
+    /// Checks the internal consistency of the configuration.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let bounds = &self.rollback_delay_bounds;
+        if bounds.start >= bounds.end {
+            return Err(ConfigError::EmptyRollbackDelayBounds {
+                start: bounds.start,
+                end: bounds.end,
+            });
+        }
+        let delay = self.default_rollback_delay;
+        if delay < bounds.start || delay >= bounds.end {
+            return Err(ConfigError::DefaultRollbackDelayOutOfBounds {
+                delay,
+                start: bounds.start,
+                end: bounds.end,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Error in the service configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Fail)]
+pub enum ConfigError {
+    /// `rollback_delay_bounds` is an empty range.
+    #[fail(display = "rollback delay bounds {}..{} are empty", start, end)]
+    EmptyRollbackDelayBounds {
+        /// Lower bound of the range (inclusive).
+        start: u32,
+        /// Upper bound of the range (exclusive).
+        end: u32,
+    },
+
+    /// `default_rollback_delay` lies outside of `rollback_delay_bounds`.
+    #[fail(
+        display = "default rollback delay {} is outside of bounds {}..{}",
+        delay,
+        start,
+        end
+    )]
+    DefaultRollbackDelayOutOfBounds {
+        /// The default rollback delay.
+        delay: u32,
+        /// Lower bound of the range (inclusive).
+        start: u32,
+        /// Upper bound of the range (exclusive).
+        end: u32,
+    },
+}
+
+/// Panics if the service configuration is invalid.
+fn validate_config(config: &Config) {
+    if let Err(e) = config.validate() {
+        panic!("invalid private currency service configuration: {}", e);
+    }
 }
 
 /// Privacy-preserving cryptocurrency service.
 ///
 /// See crate documentation for more details.
-#[derive(Debug, Default)]
+///
+/// # Panics
+///
+/// Creating a service panics if [`CONFIG`] is invalid.
+///
+/// [`CONFIG`]: constant.CONFIG.html
+#[derive(Debug)]
 pub struct Service {
+    config: Arc<Config>,
     debugger_probe: Option<DebuggerProbe>,
 }
 
+impl Default for Service {
+    fn default() -> Self {
+        Service::with_config(CONFIG)
+    }
+}
+
 impl Service {
+    /// Creates a service with the specified configuration instead of [`CONFIG`].
+    ///
+    /// The configuration determines transaction verification and execution, and the values
+    /// returned by the HTTP API. `initial_balance` and `min_transfer_amount` are exceptions:
+    /// they are built into wallets created by [`SecretState`] and thus are always taken
+    /// from [`CONFIG`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configuration is invalid.
+    ///
+    /// [`CONFIG`]: constant.CONFIG.html
+    /// [`SecretState`]: struct.SecretState.html
+    pub fn with_config(config: Config) -> Self {
+        validate_config(&config);
+        Service {
+            config: Arc::new(config),
+            debugger_probe: None,
+        }
+    }
+
     /// Creates a service with an attached debugger.
     ///
     /// The service created in this way has high associated performance penalty. Use for
     /// debugging only; otherwise, use `Service::default()`.
     pub fn debug(options: DebuggerOptions) -> (Self, Debugger) {
+        validate_config(&CONFIG);
         let (probe, debugger) = DebuggerProbe::create_channel(16, options);
         let service = Service {
+            config: Arc::new(CONFIG),
             debugger_probe: Some(probe),
         };
         (service, debugger)
@@ -123,7 +272,9 @@ impl bc::Service for Service {
 
     fn tx_from_raw(&self, raw: RawMessage) -> Result<Box<Transaction>, EncodingError> {
         use bc::TransactionSet;
-        Transactions::tx_from_raw(raw).map(|tx| tx.into())
+        let config = Arc::clone(&self.config);
+        Transactions::tx_from_raw(raw)
+            .map(|tx| Box::new(ConfiguredTransaction::new(tx, config)) as Box<dyn Transaction>)
     }
 
     fn before_commit(&self, fork: &mut Fork) {
@@ -135,14 +286,95 @@ impl bc::Service for Service {
 
     fn after_commit(&self, context: &ServiceContext) {
         if let Some(ref probe) = self.debugger_probe {
-            probe.on_after_commit(context);
+            probe.on_after_commit(context, &self.config);
         }
     }
 
     fn wire_api(&self, builder: &mut ServiceApiBuilder) {
+        let config = Arc::clone(&self.config);
+        let config_endpoint =
+            move |state: &ServiceApiState, query: ()| Api::config(&config, state, query);
+        let config = Arc::clone(&self.config);
+        let transfer_endpoint = move |state: &ServiceApiState, query: TransferQuery| {
+            Api::transfer(&config, state, query)
+        };
+        let config = Arc::clone(&self.config);
+        let transaction_endpoint =
+            move |state: &ServiceApiState, tx: Transactions| Api::transaction(&config, state, tx);
+        let config = Arc::clone(&self.config);
+        let state_hash_endpoint = move |state: &ServiceApiState, query: ()| {
+            Api::state_hash_components(&config, state, query)
+        };
+
         builder
             .public_scope()
             .endpoint("v1/wallet", Api::wallet)
-            .endpoint_mut("v1/transaction", Api::transaction);
+            .endpoint("v1/wallet/encryption-key", Api::encryption_key)
+            .endpoint("v1/wallet/pending-transfers", Api::pending_transfers)
+            .endpoint("v1/alias", Api::alias)
+            .endpoint("v1/config", config_endpoint)
+            .endpoint("v1/transfer", transfer_endpoint)
+            .endpoint_mut("v1/wallets/proofs", Api::wallets)
+            .endpoint_mut("v1/transaction", transaction_endpoint);
+        builder
+            .private_scope()
+            .endpoint("v1/debug/state-hash", state_hash_endpoint)
+            .endpoint("v1/debug/latest-block", Api::latest_block)
+            .endpoint("v1/debug/block", Api::block)
+            .endpoint("v1/debug/signing-preimage", Api::signing_preimage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(CONFIG.validate(), Ok(()));
+        let config = Config {
+            rollback_delay_bounds: 10..11,
+            default_rollback_delay: 10,
+            ..CONFIG
+        };
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let config = Config {
+            rollback_delay_bounds: 10..10,
+            default_rollback_delay: 10,
+            ..CONFIG
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::EmptyRollbackDelayBounds { start: 10, end: 10 })
+        );
+
+        let config = Config {
+            rollback_delay_bounds: 20..10,
+            default_rollback_delay: 15,
+            ..CONFIG
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::EmptyRollbackDelayBounds { start: 20, end: 10 })
+        );
+
+        for &delay in &[0, 4, 1_000, u32::max_value()] {
+            let config = Config {
+                default_rollback_delay: delay,
+                ..CONFIG
+            };
+            assert_eq!(
+                config.validate(),
+                Err(ConfigError::DefaultRollbackDelayOutOfBounds {
+                    delay,
+                    start: 5,
+                    end: 1_000,
+                })
+            );
+        }
     }
 }
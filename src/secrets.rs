@@ -14,14 +14,18 @@
 
 //! Utilities for managing the secret state of a wallet.
 
-use exonum::crypto::{gen_keypair, CryptoHash, PublicKey, SecretKey};
+use byteorder::{ByteOrder, LittleEndian};
+use exonum::crypto::{gen_keypair, CryptoHash, PublicKey, SecretKey, PUBLIC_KEY_LENGTH};
 
 use std::fmt;
 
-use super::CONFIG;
+use super::{Config, CONFIG};
 use crypto::{enc, Commitment, Opening, SimpleRangeProof};
 use storage::WalletInfo;
-use transactions::{Accept, CreateWallet, Transfer};
+use transactions::{
+    is_valid_alias, Accept, AcceptWithReceipt, Commit, CreateWallet, DelegateAcceptance, PreAccept,
+    RegisterAlias, Reject, Transfer,
+};
 
 lazy_static! {
     /// Opening to a minimum transfer amount.
@@ -44,6 +48,35 @@ encoding_struct! {
 }
 
 impl EncryptedData {
+    /// Checks if this data is structurally valid encrypted data for a `Transfer`, i.e.,
+    /// has a nonce of the correct length and a ciphertext of the length corresponding
+    /// to an encrypted `Opening` together with the authentication tag.
+    ///
+    /// The check does not require decryption keys; it does not guarantee that the data
+    /// can be decrypted by the receiver of the transfer.
+    pub fn is_well_formed(&self) -> bool {
+        self.nonce().len() == enc::NONCEBYTES
+            && self.encrypted_data().len() == Opening::BYTE_SIZE + enc::MACBYTES
+    }
+
+    /// Checks if this data is a structurally valid receipt for an `AcceptWithReceipt`, i.e.,
+    /// has a nonce of the correct length and a ciphertext corresponding to at most
+    /// [`max_receipt_len`] bytes of data together with the authentication tag.
+    ///
+    /// [`max_receipt_len`]: ::Config::max_receipt_len
+    pub fn is_well_formed_receipt(&self) -> bool {
+        self.is_receipt_within(CONFIG.max_receipt_len)
+    }
+
+    /// Same as `is_well_formed_receipt()`, but with an explicitly specified limit
+    /// on the receipt length.
+    pub(crate) fn is_receipt_within(&self, max_receipt_len: u32) -> bool {
+        let len = self.encrypted_data().len();
+        self.nonce().len() == enc::NONCEBYTES
+            && len >= enc::MACBYTES
+            && len - enc::MACBYTES <= max_receipt_len as usize
+    }
+
     /// Encrypts data based on sender’s private encryption key
     /// and the receiver’s public one.
     fn seal(message: &[u8], receiver: &enc::PublicKey, sender_sk: &enc::SecretKey) -> Self {
@@ -132,6 +165,67 @@ impl VerifiedTransfer {
     }
 }
 
+/// Attestation that a wallet holds at least a certain amount of spendable funds.
+///
+/// # Pending outgoing transfers
+///
+/// An outgoing transfer is debited from the sender’s balance as soon as it is committed;
+/// if it is not accepted in time, the amount is refunded. Thus, the balance commitment stored
+/// in the wallet is `Comm(bal) - Σ Comm(pending_outgoing)` for the gross balance `bal`,
+/// and the attestation proves `bal - Σ pending_outgoing >= threshold` with a single
+/// range proof for `Comm(bal) - Σ Comm(pending_outgoing) - Comm(threshold)`.
+#[derive(Debug, Clone)]
+pub struct FundsAttestation {
+    /// Public key of the attested wallet.
+    pub public_key: PublicKey,
+    /// Lower bound on the available funds.
+    pub threshold: u64,
+    proof: SimpleRangeProof,
+}
+
+impl FundsAttestation {
+    /// Attempts to deserialize an attestation from a slice.
+    pub fn from_slice(slice: &[u8]) -> Option<Self> {
+        const PROOF_OFFSET: usize = PUBLIC_KEY_LENGTH + 8;
+        if slice.len() < PROOF_OFFSET {
+            return None;
+        }
+
+        Some(FundsAttestation {
+            public_key: PublicKey::from_slice(&slice[..PUBLIC_KEY_LENGTH])?,
+            threshold: LittleEndian::read_u64(&slice[PUBLIC_KEY_LENGTH..PROOF_OFFSET]),
+            proof: SimpleRangeProof::from_slice(&slice[PROOF_OFFSET..])?,
+        })
+    }
+
+    /// Serializes this attestation to bytes, so that it can be passed to an off-chain party.
+    ///
+    /// # Implementation details
+    ///
+    /// Serialization consists of the public key of the wallet (32 bytes), the threshold
+    /// (8 bytes, little-endian) and the [serialized range proof].
+    ///
+    /// [serialized range proof]: ::crypto::SimpleRangeProof::to_bytes()
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PUBLIC_KEY_LENGTH + 8);
+        bytes.extend_from_slice(self.public_key.as_ref());
+        let mut threshold = [0_u8; 8];
+        LittleEndian::write_u64(&mut threshold, self.threshold);
+        bytes.extend_from_slice(&threshold);
+        bytes.extend_from_slice(&self.proof.to_bytes());
+        bytes
+    }
+
+    /// Verifies the attestation against the public info about a wallet, which should be
+    /// obtained from the blockchain (e.g., via [HTTP API]).
+    ///
+    /// [HTTP API]: ::api::Api::wallet()
+    pub fn verify(&self, wallet: &WalletInfo) -> bool {
+        let available_funds = &wallet.balance - &Commitment::with_no_blinding(self.threshold);
+        wallet.public_key == self.public_key && self.proof.verify(&available_funds)
+    }
+}
+
 impl SecretState {
     /// Creates an uninitialized state. The keypair for cryptographic operations
     /// is generated randomly.
@@ -162,6 +256,29 @@ impl SecretState {
         self.balance_opening.value
     }
 
+    /// Attests that the wallet has at least `threshold` of available funds.
+    ///
+    /// The state should be synchronized with the wallet history for the attestation
+    /// to verify; see [`FundsAttestation`] for details.
+    ///
+    /// # Return value
+    ///
+    /// Returns `None` if the balance is lesser than `threshold`, or if creating the range proof
+    /// has failed.
+    ///
+    /// [`FundsAttestation`]: ::FundsAttestation
+    pub fn attest_available_funds(&self, threshold: u64) -> Option<FundsAttestation> {
+        if self.balance_opening.value < threshold {
+            return None;
+        }
+        let available_funds = &self.balance_opening - &Opening::with_no_blinding(threshold);
+        Some(FundsAttestation {
+            public_key: self.verifying_key,
+            threshold,
+            proof: SimpleRangeProof::prove(&available_funds)?,
+        })
+    }
+
     /// Produces a `CreateWallet` transaction for this wallet.
     pub fn create_wallet(&self) -> CreateWallet {
         CreateWallet::new(&self.verifying_key, &self.signing_key)
@@ -185,7 +302,122 @@ impl SecretState {
         receiver: &PublicKey,
         rollback_delay: u32,
     ) -> Transfer {
-        Transfer::create(amount, receiver, rollback_delay, self).expect("creating transfer failed")
+        self.create_transfer_with_config(amount, receiver, rollback_delay, &CONFIG)
+    }
+
+    /// Produces a `Transfer` transaction for a service created with a custom configuration
+    /// (see [`Service::with_config`]). Unlike [`create_transfer`], `rollback_delay` is checked
+    /// against the `rollback_delay_bounds` of `config` rather than of [`CONFIG`].
+    ///
+    /// # Panics
+    ///
+    /// This method will panic under the same conditions as [`create_transfer`].
+    ///
+    /// [`Service::with_config`]: ::Service::with_config
+    /// [`create_transfer`]: #method.create_transfer
+    /// [`CONFIG`]: ::CONFIG
+    pub fn create_transfer_with_config(
+        &self,
+        amount: u64,
+        receiver: &PublicKey,
+        rollback_delay: u32,
+        config: &Config,
+    ) -> Transfer {
+        Transfer::create(amount, receiver, rollback_delay, self, config)
+            .expect("creating transfer failed")
+    }
+
+    /// Produces a `Reject` transaction for an incoming transfer.
+    pub fn create_reject(&self, transfer: &Transfer) -> Reject {
+        assert_eq!(*transfer.to(), self.verifying_key, "not an incoming transfer");
+        Reject::new(&self.verifying_key, &transfer.hash(), &self.signing_key)
+    }
+
+    /// Produces an `AcceptWithReceipt` transaction for an incoming transfer. The `receipt`
+    /// is encrypted for the sender of the transfer, who can decrypt it with
+    /// [`open_receipt()`](#method.open_receipt).
+    ///
+    /// The transfer should be [verified] beforehand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `receipt` is longer than [`max_receipt_len`].
+    ///
+    /// [verified]: #method.verify_transfer
+    /// [`max_receipt_len`]: ::Config::max_receipt_len
+    pub fn create_accept_with_receipt(
+        &self,
+        transfer: &Transfer,
+        receipt: &[u8],
+    ) -> AcceptWithReceipt {
+        assert_eq!(*transfer.to(), self.verifying_key, "not an incoming transfer");
+        assert!(
+            receipt.len() <= CONFIG.max_receipt_len as usize,
+            "receipt is too long"
+        );
+        let sender = enc::pk_from_ed25519(*transfer.from());
+        let receipt = EncryptedData::seal(receipt, &sender, &self.encryption_sk);
+        AcceptWithReceipt::new(
+            &self.verifying_key,
+            &transfer.hash(),
+            receipt,
+            &self.signing_key,
+        )
+    }
+
+    /// Decrypts a receipt attached by the receiver of an outgoing transfer. The receipt
+    /// may be obtained with [`Schema::receipt()`].
+    ///
+    /// # Return value
+    ///
+    /// Returns `None` if the receipt cannot be decrypted, e.g., because it was not
+    /// encrypted by the receiver of the transfer.
+    ///
+    /// [`Schema::receipt()`]: ::storage::Schema::receipt()
+    pub fn open_receipt(&self, transfer: &Transfer, receipt: &EncryptedData) -> Option<Vec<u8>> {
+        assert_eq!(*transfer.from(), self.verifying_key, "not an outgoing transfer");
+        let receiver = enc::pk_from_ed25519(*transfer.to());
+        receipt.open(&receiver, &self.encryption_sk)
+    }
+
+    /// Produces a `DelegateAcceptance` transaction, which authorizes `delegate` to accept
+    /// incoming transfers on behalf of this wallet with `DelegatedAccept` transactions.
+    pub fn delegate_acceptance(&self, delegate: &PublicKey) -> DelegateAcceptance {
+        DelegateAcceptance::new(&self.verifying_key, delegate, false, &self.signing_key)
+    }
+
+    /// Produces a `DelegateAcceptance` transaction, which revokes the authorization
+    /// of `delegate` to accept incoming transfers on behalf of this wallet.
+    pub fn revoke_acceptance_delegate(&self, delegate: &PublicKey) -> DelegateAcceptance {
+        DelegateAcceptance::new(&self.verifying_key, delegate, true, &self.signing_key)
+    }
+
+    /// Produces a `RegisterAlias` transaction for this wallet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alias` is not [valid](::transactions::is_valid_alias()).
+    pub fn register_alias(&self, alias: &str) -> RegisterAlias {
+        assert!(is_valid_alias(alias), "invalid alias: {}", alias);
+        RegisterAlias::new(&self.verifying_key, alias, &self.signing_key)
+    }
+
+    /// Produces a `PreAccept` transaction for an incoming transfer, which starts
+    /// the two-phase acceptance of the transfer.
+    ///
+    /// The transfer should be [verified] beforehand.
+    ///
+    /// [verified]: #method.verify_transfer
+    pub fn create_pre_accept(&self, transfer: &Transfer) -> PreAccept {
+        assert_eq!(*transfer.to(), self.verifying_key, "not an incoming transfer");
+        PreAccept::new(&self.verifying_key, &transfer.hash(), &self.signing_key)
+    }
+
+    /// Produces a `Commit` transaction for a pre-accepted outgoing transfer, which completes
+    /// the two-phase acceptance of the transfer.
+    pub fn create_commit(&self, transfer: &Transfer) -> Commit {
+        assert_eq!(*transfer.from(), self.verifying_key, "not an outgoing transfer");
+        Commit::new(&self.verifying_key, &transfer.hash(), &self.signing_key)
     }
 
     /// Initializes the state.
@@ -206,19 +438,20 @@ impl SecretState {
     /// # Return value
     ///
     /// Returns the decrypted opening for the transferred amount, or `None` if it cannot
-    /// be decrypted from the transfer.
+    /// be decrypted from the transfer or does not match the committed amount.
     pub fn verify_transfer(&self, transfer: &Transfer) -> Option<VerifiedTransfer> {
         if self.verifying_key == *transfer.to() {
             let sender = enc::pk_from_ed25519(*transfer.from());
             let opening = transfer
                 .encrypted_data()
                 .open(&sender, &self.encryption_sk)?;
+            let opening = Opening::from_slice(&opening)?;
+            if !transfer.amount().verify(&opening) {
+                return None;
+            }
 
             let accept = Accept::new(&self.verifying_key, &transfer.hash(), &self.signing_key);
-            Some(VerifiedTransfer {
-                opening: Opening::from_slice(&opening)?,
-                accept,
-            })
+            Some(VerifiedTransfer { opening, accept })
         } else {
             None
         }
@@ -299,9 +532,10 @@ impl Transfer {
         receiver: &PublicKey,
         rollback_delay: u32,
         sender_secrets: &SecretState,
+        config: &Config,
     ) -> Option<Self> {
-        assert!(CONFIG.rollback_delay_bounds.start <= rollback_delay);
-        assert!(rollback_delay < CONFIG.rollback_delay_bounds.end);
+        assert!(config.rollback_delay_bounds.start <= rollback_delay);
+        assert!(rollback_delay < config.rollback_delay_bounds.end);
         assert!(amount >= CONFIG.min_transfer_amount);
         assert!(sender_secrets.balance_opening.value >= amount);
         assert_ne!(receiver, sender_secrets.public_key());
@@ -361,6 +595,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encrypted_data_structure() {
+        let sender_sec = gen_wallet(100);
+        let receiver = gen_wallet(50).to_public();
+        let transfer =
+            Transfer::create(42, &receiver.public_key, 10, &sender_sec, &CONFIG).expect("transfer");
+        let encrypted_data = transfer.encrypted_data();
+        assert!(encrypted_data.is_well_formed());
+
+        let nonce = encrypted_data.nonce();
+        let ciphertext = encrypted_data.encrypted_data();
+        let truncated = &ciphertext[..ciphertext.len() - 1];
+        let mut extended = ciphertext.to_vec();
+        extended.push(0);
+        let malformed = vec![
+            EncryptedData::new(&[], ciphertext),
+            EncryptedData::new(&nonce[1..], ciphertext),
+            EncryptedData::new(nonce, &[]),
+            EncryptedData::new(nonce, truncated),
+            EncryptedData::new(nonce, &extended),
+            EncryptedData::new(nonce, &ciphertext[..enc::MACBYTES]),
+        ];
+        for data in malformed {
+            assert!(!data.is_well_formed());
+
+            let transfer = Transfer::new(
+                &sender_sec.verifying_key,
+                &receiver.public_key,
+                transfer.rollback_delay(),
+                transfer.history_len(),
+                transfer.amount(),
+                transfer.amount_proof(),
+                transfer.sufficient_balance_proof(),
+                data,
+                &sender_sec.signing_key,
+            );
+            assert!(!transfer.verify());
+        }
+    }
+
     #[test]
     fn transfer_verifies() {
         let sender_sec = gen_wallet(100);
@@ -369,7 +643,7 @@ mod tests {
         let receiver = receiver_sec.to_public();
 
         let transfer =
-            Transfer::create(42, &receiver.public_key, 10, &sender_sec).expect("transfer");
+            Transfer::create(42, &receiver.public_key, 10, &sender_sec, &CONFIG).expect("transfer");
         assert!(transfer.verify_stateless());
         assert!(transfer.verify_stateful(&sender.balance));
 
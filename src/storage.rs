@@ -14,18 +14,22 @@
 
 //! Storage logic for the service.
 
+use byteorder::{ByteOrder, LittleEndian};
 use exonum::{
     blockchain::Schema as CoreSchema,
-    crypto::{CryptoHash, Hash, PublicKey},
+    crypto::{hash, CryptoHash, Hash, HashStream, PublicKey},
     helpers::Height,
     messages::Message,
-    storage::{Fork, KeySetIndex, ProofListIndex, ProofMapIndex, Snapshot, SparseListIndex},
+    storage::{
+        Fork, KeySetIndex, MapIndex, ProofListIndex, ProofMapIndex, Snapshot, SparseListIndex,
+    },
 };
 
 use std::collections::{HashMap, HashSet};
 
-use super::CONFIG;
+use super::{Config, CONFIG};
 use crypto::{enc, Commitment};
+use secrets::EncryptedData;
 use transactions::{CreateWallet, Error, Transfer};
 
 const WALLETS: &str = "private_currency.wallets";
@@ -33,6 +37,26 @@ const HISTORY: &str = "private_currency.history";
 const UNACCEPTED_PAYMENTS: &str = "private_currency.unaccepted_payments";
 const ROLLBACK_BY_HEIGHT: &str = "private_currency.rollback_by_height";
 const PAST_BALANCES: &str = "private_currency.past_balances";
+const PRE_ACCEPTED: &str = "private_currency.pre_accepted";
+const ACCEPTANCE_DELEGATES: &str = "private_currency.acceptance_delegates";
+const ALIASES: &str = "private_currency.aliases";
+const WALLET_ALIASES: &str = "private_currency.wallet_aliases";
+const RECEIPTS: &str = "private_currency.receipts";
+const ACCEPTED_TRANSFERS: &str = "private_currency.accepted_transfers";
+const REJECTED_TRANSFERS: &str = "private_currency.rejected_transfers";
+
+/// Index of the wallets table in the [service state hash](struct.Schema.html#method.state_hash).
+pub const WALLETS_TABLE_INDEX: usize = 0;
+/// Index of the pre-accepted transfers table in the
+/// [service state hash](struct.Schema.html#method.state_hash).
+pub const PRE_ACCEPTED_TABLE_INDEX: usize = 1;
+/// Index of the acceptance delegates table in the
+/// [service state hash](struct.Schema.html#method.state_hash).
+pub const ACCEPTANCE_DELEGATES_TABLE_INDEX: usize = 2;
+/// Index of the aliases table in the [service state hash](struct.Schema.html#method.state_hash).
+pub const ALIASES_TABLE_INDEX: usize = 3;
+/// Index of the receipts table in the [service state hash](struct.Schema.html#method.state_hash).
+pub const RECEIPTS_TABLE_INDEX: usize = 4;
 
 lazy_static! {
     /// Commitment to the initial balance of a wallet.
@@ -91,6 +115,11 @@ impl Event {
     pub fn rollback(id: &Hash) -> Self {
         Event::new(EventTag::Rollback as u8, id)
     }
+
+    /// Creates a new event of wallet initialization by an incoming transfer.
+    pub fn implicit_create_wallet(id: &Hash) -> Self {
+        Event::new(EventTag::ImplicitCreateWallet as u8, id)
+    }
 }
 
 /// Tag used in `Event`s.
@@ -103,6 +132,8 @@ pub(crate) enum EventTag {
     Transfer = 1,
     /// Transfer rollback.
     Rollback = 2,
+    /// Wallet initialization by an incoming transfer to an unregistered key.
+    ImplicitCreateWallet = 3,
 }
 
 /// Gist of information about the wallet, stripped of auxiliary data.
@@ -181,6 +212,22 @@ impl Wallet {
     }
 }
 
+/// Computes the key corresponding to an alias in the [aliases table](self::Schema::aliases()).
+pub fn alias_key(alias: &str) -> Hash {
+    hash(alias.as_bytes())
+}
+
+/// Computes the key corresponding to an authorization of the `delegate` to accept transfers
+/// on behalf of the `owner` in the [acceptance delegates table].
+///
+/// [acceptance delegates table]: self::Schema::acceptance_delegates()
+pub fn delegation_key(owner: &PublicKey, delegate: &PublicKey) -> Hash {
+    HashStream::new()
+        .update(owner.as_ref())
+        .update(delegate.as_ref())
+        .hash()
+}
+
 /// Loads a `CreateWallet` transaction with the specified hash from a storage snapshot.
 ///
 /// # Return value
@@ -217,6 +264,87 @@ where
     Transfer::from_raw(transaction).ok()
 }
 
+/// Inconsistency in the rollback queue, i.e., the index of unaccepted transfers keyed by
+/// the height at which they should be rolled back.
+///
+/// See [`Schema::verify_rollback_queue_integrity()`] for details.
+///
+/// [`Schema::verify_rollback_queue_integrity()`]: self::Schema::verify_rollback_queue_integrity()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+pub enum RollbackError {
+    /// An unaccepted transfer does not reference a committed `Transfer` transaction.
+    #[fail(display = "unaccepted transfer {:?} is not committed", transfer_id)]
+    UnknownTransfer {
+        /// Hash of the transfer.
+        transfer_id: Hash,
+    },
+
+    /// An unaccepted transfer should have already been rolled back.
+    #[fail(
+        display = "unaccepted transfer {:?} should have been rolled back at height {}",
+        transfer_id,
+        height
+    )]
+    Overdue {
+        /// Hash of the transfer.
+        transfer_id: Hash,
+        /// Height at which the transfer should have been rolled back.
+        height: Height,
+    },
+
+    /// An unaccepted transfer is not scheduled for rollback.
+    #[fail(display = "unaccepted transfer {:?} is not scheduled for rollback", transfer_id)]
+    Missing {
+        /// Hash of the transfer.
+        transfer_id: Hash,
+    },
+
+    /// A transfer is scheduled for rollback at an unexpected height.
+    #[fail(
+        display = "transfer {:?} is scheduled for rollback at height {} instead of {}",
+        transfer_id,
+        actual,
+        expected
+    )]
+    Misplaced {
+        /// Hash of the transfer.
+        transfer_id: Hash,
+        /// Height at which the transfer should be rolled back.
+        expected: Height,
+        /// Height at which the transfer is scheduled to be rolled back.
+        actual: Height,
+    },
+
+    /// A transfer is scheduled for rollback more than once.
+    #[fail(
+        display = "transfer {:?} is scheduled for rollback both at height {} and {}",
+        transfer_id,
+        first,
+        second
+    )]
+    Duplicate {
+        /// Hash of the transfer.
+        transfer_id: Hash,
+        /// Lesser of the heights at which the transfer is scheduled to be rolled back.
+        first: Height,
+        /// Greater of the heights at which the transfer is scheduled to be rolled back.
+        second: Height,
+    },
+
+    /// A transfer scheduled for rollback is not among unaccepted transfers.
+    #[fail(
+        display = "transfer {:?} scheduled for rollback at height {} is not unaccepted",
+        transfer_id,
+        height
+    )]
+    Dangling {
+        /// Hash of the transfer.
+        transfer_id: Hash,
+        /// Height at which the transfer is scheduled to be rolled back.
+        height: Height,
+    },
+}
+
 /// Schema for the private currency service.
 #[derive(Debug)]
 pub struct Schema<T> {
@@ -231,13 +359,101 @@ impl<T: AsRef<dyn Snapshot>> Schema<T> {
 
     /// Returns the state hash of the service.
     ///
-    /// The state hash directly commits to a single table of the service, wallets.
-    /// Other Merkelized tables (wallet histories and unaccepted transfers) are connected
-    /// to the state via fields in [`Wallet`] records.
+    /// The state hash directly commits to five tables of the service: wallets,
+    /// pre-accepted transfers, acceptance delegates, aliases and receipts, in this order;
+    /// the position of each table is given by the `*_TABLE_INDEX` constants,
+    /// e.g., [`WALLETS_TABLE_INDEX`]. Other Merkelized tables (wallet histories
+    /// and unaccepted transfers) are connected to the state via fields in [`Wallet`] records.
+    /// Digests of the tables not committed to directly are available
+    /// via [`auxiliary_hash_components()`](#method.auxiliary_hash_components).
+    ///
+    /// # Compatibility
+    ///
+    /// Earlier versions of the service committed only to the wallets table. The state hash
+    /// changes for all blocks, including the genesis block, and there is no activation height
+    /// for the change, so all nodes of a network must run the same version; an existing
+    /// blockchain cannot be continued by nodes with the new version.
     ///
+    /// [`WALLETS_TABLE_INDEX`]: constant.WALLETS_TABLE_INDEX.html
     /// [`Wallet`]: self::Wallet
     pub fn state_hash(&self) -> Vec<Hash> {
-        vec![self.wallets().merkle_root()]
+        self.state_hash_components()
+            .into_iter()
+            .map(|(_, hash)| hash)
+            .collect()
+    }
+
+    /// Returns the components of the [state hash](#method.state_hash) together with
+    /// the names of the corresponding tables, in the same order as in the state hash
+    /// (i.e., the component for the wallets table is at [`WALLETS_TABLE_INDEX`], etc.).
+    ///
+    /// This is useful for debugging consensus divergence: comparing components
+    /// returned by different nodes pinpoints the diverged table.
+    ///
+    /// [`WALLETS_TABLE_INDEX`]: constant.WALLETS_TABLE_INDEX.html
+    pub fn state_hash_components(&self) -> Vec<(String, Hash)> {
+        // The order must correspond to the `*_TABLE_INDEX` constants.
+        vec![
+            (WALLETS.to_owned(), self.wallets().merkle_root()),
+            (
+                PRE_ACCEPTED.to_owned(),
+                self.pre_accepted_transfers().merkle_root(),
+            ),
+            (
+                ACCEPTANCE_DELEGATES.to_owned(),
+                self.acceptance_delegates().merkle_root(),
+            ),
+            (ALIASES.to_owned(), self.aliases().merkle_root()),
+            (RECEIPTS.to_owned(), self.receipts().merkle_root()),
+        ]
+    }
+
+    /// Returns digests of the tables that are not directly committed to by the
+    /// [state hash](#method.state_hash), together with the names of the tables:
+    ///
+    /// - Unaccepted transfers of all wallets. The digest covers the public key of each wallet
+    ///   and the Merkle root of its unaccepted transfers, computed from the table itself
+    ///   rather than taken from the [`Wallet`] record.
+    /// - The rollback queue for heights starting from the current one, up to the maximum
+    ///   rollback delay in `config`, which should be the configuration the service runs with.
+    ///   The queue is not
+    ///   Merkelized and is not a part of the state hash, since it is fully determined
+    ///   by the unaccepted transfers and the heights of blocks containing them; the digest
+    ///   covers each non-empty height together with the transfers scheduled for it.
+    ///
+    /// Like [`state_hash_components()`](#method.state_hash_components), the digests are
+    /// intended for debugging consensus divergence. Computing them iterates over all wallets
+    /// and over the rollback queue, so it is quite slow.
+    ///
+    /// [`Wallet`]: self::Wallet
+    pub fn auxiliary_hash_components(&self, config: &Config) -> Vec<(String, Hash)> {
+        let mut unaccepted = HashStream::new();
+        for wallet in self.wallets().values() {
+            let key = wallet.public_key();
+            let root = self.unaccepted_transfers_index(key).merkle_root();
+            unaccepted = unaccepted.update(key.as_ref()).update(root.as_ref());
+        }
+
+        let mut rollback_queue = HashStream::new();
+        let current_height = CoreSchema::new(&self.inner).height();
+        let max_height = current_height.0 + u64::from(config.rollback_delay_bounds.end);
+        for height in (current_height.0..=max_height).map(Height) {
+            let transfer_ids = self.rollback_transfers(height);
+            if transfer_ids.is_empty() {
+                continue;
+            }
+            let mut height_bytes = [0_u8; 8];
+            LittleEndian::write_u64(&mut height_bytes, height.0);
+            rollback_queue = rollback_queue.update(&height_bytes);
+            for transfer_id in &transfer_ids {
+                rollback_queue = rollback_queue.update(transfer_id.as_ref());
+            }
+        }
+
+        vec![
+            (UNACCEPTED_PAYMENTS.to_owned(), unaccepted.hash()),
+            (ROLLBACK_BY_HEIGHT.to_owned(), rollback_queue.hash()),
+        ]
     }
 
     /// Returns the mapping of public keys to wallets.
@@ -287,6 +503,101 @@ impl<T: AsRef<dyn Snapshot>> Schema<T> {
         self.past_balances(key).get(index)
     }
 
+    /// Checks if one of the outgoing transfers among the last `window` entries
+    /// in the history of the `sender` has the specified encrypted data.
+    pub(crate) fn has_recent_encrypted_data(
+        &self,
+        sender: &PublicKey,
+        encrypted_data: &EncryptedData,
+        window: u64,
+    ) -> bool {
+        let history = self.history_index(sender);
+        let len = history.len();
+        (len.saturating_sub(window)..len)
+            .filter_map(|i| history.get(i))
+            .filter(|event| event.tag() == EventTag::Transfer as u8)
+            .filter_map(|event| maybe_transfer(&self.inner, event.transaction_hash()))
+            .any(|transfer| {
+                transfer.from() == sender && transfer.encrypted_data() == *encrypted_data
+            })
+    }
+
+    /// Returns the mapping of pre-accepted transfers to the hashes of the corresponding
+    /// `PreAccept` transactions.
+    ///
+    /// Pre-accepted transfers remain in the unaccepted transfers of the receiver
+    /// until they are committed by the sender or rolled back.
+    pub fn pre_accepted_transfers(&self) -> ProofMapIndex<&T, Hash, Hash> {
+        ProofMapIndex::new(PRE_ACCEPTED, &self.inner)
+    }
+
+    /// Returns the set of authorizations for acceptance delegates to accept transfers
+    /// on behalf of wallets.
+    ///
+    /// Use [`delegation_key`](fn.delegation_key.html) to compute the key for an authorization.
+    pub fn acceptance_delegates(&self) -> ProofMapIndex<&T, Hash, ()> {
+        ProofMapIndex::new(ACCEPTANCE_DELEGATES, &self.inner)
+    }
+
+    /// Checks if the `delegate` may accept transfers on behalf of the `owner`.
+    pub fn is_acceptance_delegate(&self, owner: &PublicKey, delegate: &PublicKey) -> bool {
+        self.acceptance_delegates()
+            .contains(&delegation_key(owner, delegate))
+    }
+
+    /// Returns the mapping of alias hashes to the public keys of wallets owning the aliases.
+    ///
+    /// Use [`alias_key`](fn.alias_key.html) to compute the key for an alias.
+    pub fn aliases(&self) -> ProofMapIndex<&T, Hash, PublicKey> {
+        ProofMapIndex::new(ALIASES, &self.inner)
+    }
+
+    /// Resolves an alias into the public key of the wallet owning it.
+    pub fn resolve_alias(&self, alias: &str) -> Option<PublicKey> {
+        self.aliases().get(&alias_key(alias))
+    }
+
+    fn wallet_aliases(&self) -> MapIndex<&T, PublicKey, String> {
+        MapIndex::new(WALLET_ALIASES, &self.inner)
+    }
+
+    /// Returns the alias registered by a wallet.
+    pub fn wallet_alias(&self, key: &PublicKey) -> Option<String> {
+        self.wallet_aliases().get(key)
+    }
+
+    /// Returns the mapping of transfers accepted with
+    /// [`AcceptWithReceipt`](::transactions::AcceptWithReceipt) to the receipts attached
+    /// by their receivers.
+    pub fn receipts(&self) -> ProofMapIndex<&T, Hash, EncryptedData> {
+        ProofMapIndex::new(RECEIPTS, &self.inner)
+    }
+
+    /// Loads the receipt attached to an accepted transfer.
+    pub fn receipt(&self, transfer_id: &Hash) -> Option<EncryptedData> {
+        self.receipts().get(transfer_id)
+    }
+
+    fn accepted_transfers(&self) -> MapIndex<&T, Hash, u64> {
+        MapIndex::new(ACCEPTED_TRANSFERS, &self.inner)
+    }
+
+    /// Returns the index of the event crediting an accepted transfer in the history
+    /// of its receiver, or `None` if the transfer is not accepted.
+    pub fn accepting_event(&self, transfer_id: &Hash) -> Option<u64> {
+        self.accepted_transfers().get(transfer_id)
+    }
+
+    fn rejected_transfers(&self) -> KeySetIndex<&T, Hash> {
+        KeySetIndex::new(REJECTED_TRANSFERS, &self.inner)
+    }
+
+    /// Checks if a transfer has been explicitly rejected by its receiver
+    /// with a [`Reject`](::transactions::Reject) transaction.
+    pub fn is_rejected(&self, transfer_id: &Hash) -> bool {
+        self.rejected_transfers().contains(transfer_id)
+    }
+
     fn rollback_index(&self, height: Height) -> KeySetIndex<&T, Hash> {
         let height = height.0;
         KeySetIndex::new_in_family(ROLLBACK_BY_HEIGHT, &height, &self.inner)
@@ -301,6 +612,89 @@ impl<T: AsRef<dyn Snapshot>> Schema<T> {
         let hashes = index.iter().collect();
         hashes
     }
+
+    /// Computes the height at which a committed transfer should be rolled back if it is
+    /// not accepted.
+    pub(crate) fn scheduled_rollback_height(&self, transfer_id: &Hash) -> Option<Height> {
+        let transfer = maybe_transfer(&self.inner, transfer_id)?;
+        let height = CoreSchema::new(&self.inner)
+            .transactions_locations()
+            .get(transfer_id)?
+            .block_height();
+        Some(Height(height.0 + u64::from(transfer.rollback_delay())))
+    }
+
+    /// Checks that the rollback queue is consistent with unaccepted transfers of all wallets.
+    ///
+    /// Each unaccepted transfer must be scheduled for rollback exactly once, at the height
+    /// determined by the block containing the transfer and its `rollback_delay`; conversely,
+    /// each transfer in the queue must be unaccepted. This is an internal invariant
+    /// of the service; it may be useful to check it after storage migrations
+    /// or recovery from a crash.
+    ///
+    /// The check iterates over all wallets and over the rollback queue for all heights
+    /// that can hold entries given the maximum rollback delay in `config`, so it is quite slow.
+    /// `config` should be the configuration the service runs with; with a smaller
+    /// `rollback_delay_bounds`, transfers with longer delays would be reported as missing.
+    pub fn verify_rollback_queue_integrity(&self, config: &Config) -> Result<(), RollbackError> {
+        let current_height = CoreSchema::new(&self.inner).height();
+
+        let mut unaccepted = HashMap::new();
+        for wallet in self.wallets().values() {
+            for transfer_id in self.unaccepted_transfers_index(wallet.public_key()).keys() {
+                let height = self
+                    .scheduled_rollback_height(&transfer_id)
+                    .ok_or(RollbackError::UnknownTransfer { transfer_id })?;
+                // The transfers scheduled at the current height are rolled back
+                // in the next block.
+                if height < current_height {
+                    return Err(RollbackError::Overdue {
+                        transfer_id,
+                        height,
+                    });
+                }
+                unaccepted.insert(transfer_id, height);
+            }
+        }
+
+        let mut scheduled = HashMap::new();
+        let max_delay = u64::from(config.rollback_delay_bounds.end);
+        let min_height = current_height.0.saturating_sub(max_delay);
+        let max_height = current_height.0 + max_delay;
+        for height in (min_height..=max_height).map(Height) {
+            for transfer_id in self.rollback_index(height).iter() {
+                if let Some(&first) = scheduled.get(&transfer_id) {
+                    return Err(RollbackError::Duplicate {
+                        transfer_id,
+                        first,
+                        second: height,
+                    });
+                }
+                match unaccepted.get(&transfer_id) {
+                    None => {
+                        return Err(RollbackError::Dangling {
+                            transfer_id,
+                            height,
+                        })
+                    }
+                    Some(&expected) if expected != height => {
+                        return Err(RollbackError::Misplaced {
+                            transfer_id,
+                            expected,
+                            actual: height,
+                        })
+                    }
+                    _ => {}
+                }
+                scheduled.insert(transfer_id, height);
+            }
+        }
+
+        match unaccepted.keys().find(|id| !scheduled.contains_key(id)) {
+            Some(&transfer_id) => Err(RollbackError::Missing { transfer_id }),
+            None => Ok(()),
+        }
+    }
 }
 
 impl<'a> Schema<&'a mut Fork> {
@@ -316,6 +710,34 @@ impl<'a> Schema<&'a mut Fork> {
         ProofMapIndex::new_in_family(UNACCEPTED_PAYMENTS, key, self.inner)
     }
 
+    fn pre_accepted_transfers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Hash> {
+        ProofMapIndex::new(PRE_ACCEPTED, self.inner)
+    }
+
+    fn acceptance_delegates_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, ()> {
+        ProofMapIndex::new(ACCEPTANCE_DELEGATES, self.inner)
+    }
+
+    fn aliases_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, PublicKey> {
+        ProofMapIndex::new(ALIASES, self.inner)
+    }
+
+    fn wallet_aliases_mut(&mut self) -> MapIndex<&mut Fork, PublicKey, String> {
+        MapIndex::new(WALLET_ALIASES, self.inner)
+    }
+
+    fn receipts_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, EncryptedData> {
+        ProofMapIndex::new(RECEIPTS, self.inner)
+    }
+
+    fn accepted_transfers_mut(&mut self) -> MapIndex<&mut Fork, Hash, u64> {
+        MapIndex::new(ACCEPTED_TRANSFERS, self.inner)
+    }
+
+    fn rejected_transfers_mut(&mut self) -> KeySetIndex<&mut Fork, Hash> {
+        KeySetIndex::new(REJECTED_TRANSFERS, self.inner)
+    }
+
     fn rollback_index_mut(&mut self, height: Height) -> KeySetIndex<&mut Fork, Hash> {
         let height = height.0;
         KeySetIndex::new_in_family(ROLLBACK_BY_HEIGHT, &height, self.inner)
@@ -333,14 +755,24 @@ impl<'a> Schema<&'a mut Fork> {
         if self.wallets().contains(key) {
             return Err(Error::WalletExists);
         }
+        self.initialize_wallet(key, Event::create_wallet(&tx.hash()));
+        Ok(())
+    }
 
-        self.history_index_mut(key)
-            .push(Event::create_wallet(&tx.hash()));
+    /// Creates a wallet for the receiver of `tx`, which is assumed not to be registered.
+    /// The wallet is initialized in the same way as with `CreateWallet`.
+    pub(crate) fn create_implicit_wallet(&mut self, key: &PublicKey, tx: &Transfer) -> Wallet {
+        debug_assert!(!self.wallets().contains(key));
+        self.initialize_wallet(key, Event::implicit_create_wallet(&tx.hash()))
+    }
+
+    fn initialize_wallet(&mut self, key: &PublicKey, event: Event) -> Wallet {
+        self.history_index_mut(key).push(event);
         let history_hash = self.history_index(key).merkle_root();
         let wallet = Wallet::initialize(key, &history_hash);
         self.past_balances_mut(key).set(0, wallet.balance());
-        self.wallets_mut().put(key, wallet);
-        Ok(())
+        self.wallets_mut().put(key, wallet.clone());
+        wallet
     }
 
     pub(crate) fn update_sender(&mut self, sender: &Wallet, amount: &Commitment, tx: &Transfer) {
@@ -384,19 +816,10 @@ impl<'a> Schema<&'a mut Fork> {
     }
 
     fn rollback_height(&self, transfer_id: &Hash) -> Height {
-        let core_schema = CoreSchema::new(&self.inner);
-        let tx_location = core_schema
-            .transactions_locations()
-            .get(transfer_id)
+        let rollback_height = self
+            .scheduled_rollback_height(transfer_id)
             .expect("transfer");
-        let height = tx_location.block_height();
-        let transfer = core_schema
-            .transactions()
-            .get(transfer_id)
-            .expect("transfer");
-        let transfer = Transfer::from_raw(transfer).expect("parse transfer");
-        let rollback_height = Height(height.0 + u64::from(transfer.rollback_delay()));
-        debug_assert!(rollback_height >= core_schema.height());
+        debug_assert!(rollback_height >= CoreSchema::new(&self.inner).height());
         rollback_height
     }
 
@@ -420,6 +843,8 @@ impl<'a> Schema<&'a mut Fork> {
             payments.remove(transfer_id);
             payments.merkle_root()
         };
+        let event_index = self.history_index(receiver).len() - 1;
+        self.accepted_transfers_mut().put(transfer_id, event_index);
 
         // Update the receiver’s wallet.
         let transfer_amount = transfer.amount();
@@ -441,6 +866,111 @@ impl<'a> Schema<&'a mut Fork> {
         Ok(())
     }
 
+    pub(crate) fn add_acceptance_delegate(
+        &mut self,
+        owner: &PublicKey,
+        delegate: &PublicKey,
+    ) -> Result<(), Error> {
+        let key = delegation_key(owner, delegate);
+        let mut delegates = self.acceptance_delegates_mut();
+        if delegates.contains(&key) {
+            return Err(Error::DelegateExists);
+        }
+        delegates.put(&key, ());
+        Ok(())
+    }
+
+    pub(crate) fn remove_acceptance_delegate(
+        &mut self,
+        owner: &PublicKey,
+        delegate: &PublicKey,
+    ) -> Result<(), Error> {
+        let key = delegation_key(owner, delegate);
+        let mut delegates = self.acceptance_delegates_mut();
+        if !delegates.contains(&key) {
+            return Err(Error::UnknownDelegate);
+        }
+        delegates.remove(&key);
+        Ok(())
+    }
+
+    pub(crate) fn register_alias(&mut self, owner: &PublicKey, alias: &str) -> Result<(), Error> {
+        let alias_key = alias_key(alias);
+        if self.aliases().contains(&alias_key) {
+            return Err(Error::AliasTaken);
+        }
+        if self.wallet_aliases().contains(owner) {
+            return Err(Error::AliasAlreadyRegistered);
+        }
+        self.aliases_mut().put(&alias_key, *owner);
+        self.wallet_aliases_mut().put(owner, alias.to_owned());
+        Ok(())
+    }
+
+    pub(crate) fn add_receipt(&mut self, transfer_id: &Hash, receipt: EncryptedData) {
+        self.receipts_mut().put(transfer_id, receipt);
+    }
+
+    pub(crate) fn pre_accept_payment(
+        &mut self,
+        transfer: &Transfer,
+        transfer_id: &Hash,
+        pre_accept_id: &Hash,
+    ) -> Result<(), Error> {
+        if !self
+            .unaccepted_transfers_index(transfer.to())
+            .contains(transfer_id)
+        {
+            return Err(Error::UnknownTransfer);
+        }
+        let mut pre_accepted = self.pre_accepted_transfers_mut();
+        if pre_accepted.contains(transfer_id) {
+            return Err(Error::AlreadyPreAccepted);
+        }
+        pre_accepted.put(transfer_id, *pre_accept_id);
+        Ok(())
+    }
+
+    pub(crate) fn commit_payment(
+        &mut self,
+        transfer: &Transfer,
+        transfer_id: &Hash,
+    ) -> Result<(), Error> {
+        if !self.pre_accepted_transfers().contains(transfer_id) {
+            return Err(Error::NotPreAccepted);
+        }
+        self.pre_accepted_transfers_mut().remove(transfer_id);
+        self.accept_payment(transfer, transfer_id)
+    }
+
+    pub(crate) fn reject_payment(
+        &mut self,
+        transfer: &Transfer,
+        transfer_id: &Hash,
+    ) -> Result<(), Error> {
+        let receiver = transfer.to();
+        let unaccepted_transfers_hash = {
+            let mut payments = self.unaccepted_transfers_mut(receiver);
+            if !payments.contains(transfer_id) {
+                return Err(Error::UnknownTransfer);
+            }
+            payments.remove(transfer_id);
+            payments.merkle_root()
+        };
+        let receiver_wallet = self.wallet(receiver).ok_or(Error::UnregisteredReceiver)?;
+        let receiver_wallet =
+            receiver_wallet.set_unaccepted_transfers_hash(&unaccepted_transfers_hash);
+        self.wallets_mut().put(receiver, receiver_wallet);
+
+        let rollback_height = self.rollback_height(transfer_id);
+        self.rollback_index_mut(rollback_height).remove(transfer_id);
+        self.pre_accepted_transfers_mut().remove(transfer_id);
+        self.rejected_transfers_mut().insert(*transfer_id);
+
+        self.rollback_single(transfer, transfer_id);
+        Ok(())
+    }
+
     fn rollback_single(&mut self, transfer: &Transfer, transfer_hash: &Hash) {
         // Update sender history.
         let event = Event::rollback(transfer_hash);
@@ -471,6 +1001,7 @@ impl<'a> Schema<&'a mut Fork> {
             let transfer = maybe_transfer(&self.inner, hash).expect("Transfer");
             self.rollback_single(&transfer, hash);
             self.rollback_index_mut(height).remove(hash);
+            self.pre_accepted_transfers_mut().remove(hash);
 
             let mut unaccepted_transfers = self.unaccepted_transfers_mut(transfer.to());
             unaccepted_transfers.remove(hash);
@@ -0,0 +1,89 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [`proptest`] strategies for property testing of the service and of code built on top of it.
+//!
+//! The module is available with the `testing` crate feature. Keypairs, amounts and rollback
+//! delays, as well as blinding factors of commitments generated by [`commitments()`],
+//! are derived from values generated by `proptest`, so these parts of failing cases
+//! are reproducible and can be shrunk. Range proofs and transfers are still created
+//! with the thread-local RNG; thus, the blinding factors of transfer amounts,
+//! the encryption nonces and the proofs differ between runs.
+//!
+//! [`commitments()`]: fn.commitments.html
+//!
+//! [`proptest`]: https://docs.rs/proptest/
+
+use exonum::crypto::{gen_keypair_from_seed, hash, Seed};
+use proptest::prelude::*;
+use rand::{prng::ChaChaRng, SeedableRng};
+
+use crypto::{Commitment, Opening, SimpleRangeProof};
+use secrets::SecretState;
+use transactions::Transfer;
+use CONFIG;
+
+/// Generates commitments to values produced by the `values` strategy, together
+/// with the corresponding openings.
+pub fn commitments<S>(values: S) -> impl Strategy<Value = (Commitment, Opening)>
+where
+    S: Strategy<Value = u64>,
+{
+    (values, any::<[u8; 32]>())
+        .prop_map(|(value, seed)| Commitment::with_rng(value, &mut ChaChaRng::from_seed(seed)))
+}
+
+/// Generates commitments to values produced by the `values` strategy, together
+/// with the corresponding openings and range proofs.
+pub fn range_proofs<S>(values: S) -> impl Strategy<Value = (Commitment, Opening, SimpleRangeProof)>
+where
+    S: Strategy<Value = u64>,
+{
+    commitments(values).prop_map(|(commitment, opening)| {
+        let proof = SimpleRangeProof::prove(&opening).expect("cannot create range proof");
+        (commitment, opening, proof)
+    })
+}
+
+/// Generates wallets with the initial balance, as if they were registered in the service.
+pub fn initialized_wallets() -> impl Strategy<Value = SecretState> {
+    any::<[u8; 32]>().prop_map(|seed| {
+        let (verifying_key, signing_key) = gen_keypair_from_seed(&Seed::new(seed));
+        let mut state = SecretState::from_keypair(verifying_key, signing_key);
+        state.initialize();
+        state
+    })
+}
+
+/// Generates transfers valid according to the service [`CONFIG`], together with the state
+/// of the sender. The sender is a wallet with the initial balance; the receiver is
+/// a random wallet different from the sender.
+///
+/// [`CONFIG`]: ::CONFIG
+pub fn transfers() -> impl Strategy<Value = (SecretState, Transfer)> {
+    (
+        initialized_wallets(),
+        CONFIG.min_transfer_amount..=CONFIG.initial_balance,
+        CONFIG.rollback_delay_bounds.clone(),
+    )
+        .prop_map(|(sender, amount, rollback_delay)| {
+            // Deriving the receiver from the sender ensures that the keys are different
+            // even when shrinking.
+            let receiver_seed = Seed::from_slice(hash(sender.public_key().as_ref()).as_ref())
+                .expect("invalid seed length");
+            let (receiver, _) = gen_keypair_from_seed(&receiver_seed);
+            let transfer = sender.create_transfer(amount, &receiver, rollback_delay);
+            (sender, transfer)
+        })
+}
@@ -15,13 +15,25 @@
 //! Transaction logic of the service.
 
 use exonum::{
-    blockchain::{ExecutionError, Transaction},
+    blockchain::{ExecutionError, Schema as CoreSchema, Transaction, TransactionSet},
     crypto::{Hash, PublicKey},
-    messages::Message,
+    encoding::{
+        serialize::{
+            json::{reexport::Value, ExonumJson},
+            WriteBufferWrapper,
+        },
+        Error as EncodingError,
+    },
+    helpers::Height,
+    messages::{Message, RawMessage},
     storage::Fork,
 };
 
-use super::{CONFIG, SERVICE_ID};
+use curve25519::edwards::CompressedEdwardsY;
+
+use std::{error::Error as StdError, sync::Arc};
+
+use super::{Config, CONFIG, SERVICE_ID};
 use crypto::{Commitment, SimpleRangeProof};
 use secrets::EncryptedData;
 use storage::{maybe_transfer, Schema};
@@ -36,6 +48,9 @@ transactions! {
     pub CryptoTransactions {
         const SERVICE_ID = SERVICE_ID;
 
+        // Message IDs are assigned in the declaration order, so new transactions
+        // must be added to the end of the list to keep the wire format.
+
         /// Transaction for creating a new wallet.
         ///
         /// # Notes
@@ -100,15 +115,414 @@ transactions! {
             /// Hash of the transfer transaction.
             transfer_id: &Hash,
         }
+
+        /// First phase of the two-phase transfer acceptance, in which the receiver signals
+        /// the intent to accept an incoming transfer.
+        ///
+        /// A pre-accepted transfer can no longer be [`Accept`]ed; it is credited to the receiver
+        /// only after the sender finalizes it with a [`Commit`]. Both phases need to complete
+        /// before the rollback delay of the transfer expires; otherwise, the transfer is
+        /// rolled back in the same way as an unaccepted one.
+        ///
+        /// [`Accept`]: struct.Accept.html
+        /// [`Commit`]: struct.Commit.html
+        struct PreAccept {
+            /// Public key of the receiver of the transfer.
+            receiver: &PublicKey,
+            /// Hash of the transfer transaction.
+            transfer_id: &Hash,
+        }
+
+        /// Second phase of the two-phase transfer acceptance, in which the sender finalizes
+        /// a [`PreAccept`]ed transfer, crediting it to the receiver.
+        ///
+        /// [`PreAccept`]: struct.PreAccept.html
+        struct Commit {
+            /// Public key of the sender of the transfer.
+            sender: &PublicKey,
+            /// Hash of the transfer transaction.
+            transfer_id: &Hash,
+        }
+
+        /// Transaction authorizing another key to accept incoming transfers on behalf
+        /// of a wallet with [`DelegatedAccept`], or revoking such an authorization.
+        ///
+        /// A delegate can only accept transfers; it cannot perform any other actions
+        /// on behalf of the wallet. A key may be a delegate for several wallets;
+        /// the authorization granted by one wallet does not affect other wallets.
+        ///
+        /// [`DelegatedAccept`]: struct.DelegatedAccept.html
+        struct DelegateAcceptance {
+            /// Public key of the wallet owner. The transaction must be signed with
+            /// the corresponding secret key.
+            owner: &PublicKey,
+            /// Public key of the delegate.
+            delegate: &PublicKey,
+            /// If `true`, the delegation is revoked rather than granted.
+            revoke: bool,
+        }
+
+        /// Transaction to reject an incoming transfer, immediately refunding it to the sender.
+        ///
+        /// The receiver should reject transfers for which it cannot decrypt the transferred
+        /// amount; see [`check_incoming_transfer`] for a client-side helper. A transfer
+        /// cannot be rejected once it is pre-accepted with [`PreAccept`].
+        ///
+        /// [`check_incoming_transfer`]: ::client::check_incoming_transfer()
+        /// [`PreAccept`]: self::PreAccept
+        struct Reject {
+            /// Public key of the receiver of the transfer.
+            receiver: &PublicKey,
+            /// Hash of the transfer transaction.
+            transfer_id: &Hash,
+        }
+
+        /// Transaction registering a human-readable alias for a wallet.
+        ///
+        /// Aliases are unique, and each wallet may register at most one alias. Registered
+        /// aliases cannot be changed or released. The alias must satisfy
+        /// [`is_valid_alias`].
+        ///
+        /// [`is_valid_alias`]: fn.is_valid_alias.html
+        struct RegisterAlias {
+            /// Public key of the wallet owner. The transaction must be signed with
+            /// the corresponding secret key.
+            owner: &PublicKey,
+            /// Alias to register.
+            alias: &str,
+        }
+
+        /// Transaction accepting incoming transfers into several wallets at once, e.g.,
+        /// by a custodian controlling these wallets.
+        ///
+        /// Each of `accepts` is a serialized [`Accept`] transaction signed by the receiver,
+        /// or a serialized [`DelegatedAccept`] transaction signed by an acceptance delegate
+        /// of the receiver.
+        /// The accepts are processed in order; if any of them fails, the entire transaction
+        /// fails and no transfers are accepted. The number of accepts is limited
+        /// by [`Config::max_custodial_accepts`].
+        ///
+        /// The transaction itself may be signed by any key, e.g., a key of the custodian
+        /// submitting it to the network.
+        ///
+        /// [`Accept`]: struct.Accept.html
+        /// [`DelegatedAccept`]: struct.DelegatedAccept.html
+        /// [`Config::max_custodial_accepts`]: ::Config::max_custodial_accepts
+        struct CustodialAcceptMany {
+            /// Public key of the custodian submitting the transaction.
+            custodian: &PublicKey,
+            /// Serialized `Accept` and `DelegatedAccept` transactions.
+            accepts: Vec<RawMessage>,
+        }
+
+        /// Transaction to accept an incoming transfer, attaching a receipt for the sender.
+        ///
+        /// The receipt is arbitrary data (e.g., a reference to an invoice) encrypted by
+        /// the receiver for the sender of the transfer and signed together with the rest
+        /// of the transaction. Once the transfer is accepted, the receipt is stored in
+        /// [`Schema::receipts()`], so that the sender can retrieve and decrypt it later
+        /// as a proof of acknowledgment. The length of the receipt is limited
+        /// by [`Config::max_receipt_len`].
+        ///
+        /// Otherwise, the transaction is processed in the same way as [`Accept`]
+        /// signed by the receiver.
+        ///
+        /// [`Schema::receipts()`]: ::storage::Schema::receipts()
+        /// [`Config::max_receipt_len`]: ::Config::max_receipt_len
+        /// [`Accept`]: struct.Accept.html
+        struct AcceptWithReceipt {
+            /// Public key of the receiver of the transfer.
+            receiver: &PublicKey,
+            /// Hash of the transfer transaction.
+            transfer_id: &Hash,
+            /// Receipt encrypted for the sender of the transfer.
+            receipt: EncryptedData,
+        }
+
+        /// Transaction to accept an incoming transfer on behalf of the receiver, signed
+        /// by an acceptance delegate authorized with [`DelegateAcceptance`].
+        ///
+        /// Otherwise, the transaction is processed in the same way as [`Accept`].
+        ///
+        /// [`DelegateAcceptance`]: struct.DelegateAcceptance.html
+        /// [`Accept`]: struct.Accept.html
+        struct DelegatedAccept {
+            /// Public key of the receiver of the transfer.
+            receiver: &PublicKey,
+            /// Hash of the transfer transaction.
+            transfer_id: &Hash,
+            /// Public key of the acceptance delegate. The transaction must be signed with
+            /// the corresponding secret key.
+            delegate: &PublicKey,
+        }
     }
 }
 
-impl Transaction for CreateWallet {
-    fn verify(&self) -> bool {
-        self.verify_signature(self.key())
+impl CustodialAcceptMany {
+    /// Parses the enclosed accept transactions. Returns `None` if any of them
+    /// cannot be parsed as an [`Accept`] or a [`DelegatedAccept`].
+    ///
+    /// [`Accept`]: struct.Accept.html
+    /// [`DelegatedAccept`]: struct.DelegatedAccept.html
+    pub fn parse_accepts(&self) -> Option<Vec<CryptoTransactions>> {
+        self.accepts()
+            .into_iter()
+            .map(|raw| match CryptoTransactions::tx_from_raw(raw).ok()? {
+                tx @ CryptoTransactions::Accept(_)
+                | tx @ CryptoTransactions::DelegatedAccept(_) => Some(tx),
+                _ => None,
+            })
+            .collect()
     }
+}
 
-    fn execute(&self, fork: &mut Fork) -> Result<(), ExecutionError> {
+/// Maximum length of an alias in bytes.
+pub const MAX_ALIAS_LEN: usize = 32;
+
+/// Checks if the string may be used as an alias for a wallet.
+///
+/// An alias must be 1 to [`MAX_ALIAS_LEN`] bytes long and consist only of lowercase
+/// ASCII letters, digits, `-` and `_`. The restricted char set prevents registering aliases
+/// visually indistinguishable from each other.
+///
+/// [`MAX_ALIAS_LEN`]: constant.MAX_ALIAS_LEN.html
+pub fn is_valid_alias(alias: &str) -> bool {
+    !alias.is_empty()
+        && alias.len() <= MAX_ALIAS_LEN
+        && alias.bytes().all(|byte| match byte {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => true,
+            _ => false,
+        })
+}
+
+/// Transaction types enabled in a deployment of the service.
+///
+/// Transactions of a disabled type are rejected by the [HTTP API] and fail
+/// stateless verification, so they are never included into the blockchain.
+///
+/// [HTTP API]: ::api::Api::transaction()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EnabledTransactions {
+    /// Whether [`CreateWallet`](self::CreateWallet) transactions are enabled.
+    pub create_wallet: bool,
+    /// Whether [`Transfer`](self::Transfer) transactions are enabled.
+    pub transfer: bool,
+    /// Whether [`Accept`](self::Accept) transactions are enabled.
+    pub accept: bool,
+    /// Whether two-phase acceptance (i.e., [`PreAccept`](self::PreAccept)
+    /// and [`Commit`](self::Commit) transactions) is enabled.
+    pub two_phase_accept: bool,
+    /// Whether delegated acceptance (i.e., [`DelegateAcceptance`](self::DelegateAcceptance)
+    /// and [`DelegatedAccept`](self::DelegatedAccept) transactions) is enabled.
+    pub delegate_acceptance: bool,
+    /// Whether [`Reject`](self::Reject) transactions are enabled.
+    pub reject: bool,
+    /// Whether [`RegisterAlias`](self::RegisterAlias) transactions are enabled.
+    pub register_alias: bool,
+    /// Whether [`CustodialAcceptMany`](self::CustodialAcceptMany) transactions are enabled.
+    pub custodial_accept: bool,
+    /// Whether [`AcceptWithReceipt`](self::AcceptWithReceipt) transactions are enabled.
+    pub accept_with_receipt: bool,
+}
+
+impl EnabledTransactions {
+    /// Policy with all transaction types enabled.
+    pub const ALL: Self = EnabledTransactions {
+        create_wallet: true,
+        transfer: true,
+        accept: true,
+        two_phase_accept: true,
+        delegate_acceptance: true,
+        reject: true,
+        register_alias: true,
+        custodial_accept: true,
+        accept_with_receipt: true,
+    };
+
+    /// Checks if the type of the specified transaction is enabled.
+    pub fn check(&self, transaction: &CryptoTransactions) -> Result<(), DisabledTransaction> {
+        let (is_enabled, name) = match *transaction {
+            CryptoTransactions::CreateWallet(_) => (self.create_wallet, "CreateWallet"),
+            CryptoTransactions::Transfer(_) => (self.transfer, "Transfer"),
+            CryptoTransactions::Accept(_) => (self.accept, "Accept"),
+            CryptoTransactions::PreAccept(_) => (self.two_phase_accept, "PreAccept"),
+            CryptoTransactions::Commit(_) => (self.two_phase_accept, "Commit"),
+            CryptoTransactions::Reject(_) => (self.reject, "Reject"),
+            CryptoTransactions::DelegateAcceptance(_) => {
+                (self.delegate_acceptance, "DelegateAcceptance")
+            }
+            CryptoTransactions::RegisterAlias(_) => (self.register_alias, "RegisterAlias"),
+            CryptoTransactions::CustodialAcceptMany(_) => {
+                (self.custodial_accept, "CustodialAcceptMany")
+            }
+            CryptoTransactions::AcceptWithReceipt(_) => {
+                (self.accept_with_receipt, "AcceptWithReceipt")
+            }
+            CryptoTransactions::DelegatedAccept(_) => (self.delegate_acceptance, "DelegatedAccept"),
+        };
+        if is_enabled {
+            Ok(())
+        } else {
+            Err(DisabledTransaction { name })
+        }
+    }
+}
+
+/// Error signalling that a transaction has a type disabled by [`EnabledTransactions`].
+///
+/// [`EnabledTransactions`]: self::EnabledTransactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Fail)]
+#[fail(display = "`{}` transactions are disabled in this deployment", name)]
+pub struct DisabledTransaction {
+    name: &'static str,
+}
+
+impl DisabledTransaction {
+    /// Returns the name of the disabled transaction type.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Heights from which transaction types added on top of the core transfer logic are active.
+///
+/// Scheduling activation at a future height allows to upgrade network nodes at different
+/// times without a risk of a consensus split: before the activation height, transactions
+/// of the corresponding type fail with [`InactiveTransaction`] on all nodes, regardless
+/// of whether a node supports the logic of the transaction.
+///
+/// Since the activation height is only known during transaction execution, inactive
+/// transactions pass stateless verification and are included into the blockchain
+/// as failed transactions.
+///
+/// [`InactiveTransaction`]: self::Error::InactiveTransaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ActivationHeights {
+    /// Activation height for two-phase acceptance (i.e., [`PreAccept`](self::PreAccept)
+    /// and [`Commit`](self::Commit) transactions).
+    pub two_phase_accept: u64,
+    /// Activation height for delegated acceptance (i.e.,
+    /// [`DelegateAcceptance`](self::DelegateAcceptance) and
+    /// [`DelegatedAccept`](self::DelegatedAccept) transactions).
+    pub delegate_acceptance: u64,
+    /// Activation height for [`Reject`](self::Reject) transactions.
+    pub reject: u64,
+    /// Activation height for [`RegisterAlias`](self::RegisterAlias) transactions.
+    pub register_alias: u64,
+    /// Activation height for [`CustodialAcceptMany`](self::CustodialAcceptMany) transactions.
+    pub custodial_accept: u64,
+    /// Activation height for [`AcceptWithReceipt`](self::AcceptWithReceipt) transactions.
+    pub accept_with_receipt: u64,
+}
+
+impl ActivationHeights {
+    /// All transaction types are active from the genesis block.
+    pub const GENESIS: Self = ActivationHeights {
+        two_phase_accept: 0,
+        delegate_acceptance: 0,
+        reject: 0,
+        register_alias: 0,
+        custodial_accept: 0,
+        accept_with_receipt: 0,
+    };
+
+    /// Returns the height from which the type of the specified transaction is active.
+    /// `CreateWallet`, `Transfer` and `Accept` transactions are always active.
+    pub fn activation_height(&self, transaction: &CryptoTransactions) -> Height {
+        let height = match *transaction {
+            CryptoTransactions::CreateWallet(_)
+            | CryptoTransactions::Transfer(_)
+            | CryptoTransactions::Accept(_) => 0,
+            CryptoTransactions::PreAccept(_) | CryptoTransactions::Commit(_) => {
+                self.two_phase_accept
+            }
+            CryptoTransactions::Reject(_) => self.reject,
+            CryptoTransactions::DelegateAcceptance(_) | CryptoTransactions::DelegatedAccept(_) => {
+                self.delegate_acceptance
+            }
+            CryptoTransactions::RegisterAlias(_) => self.register_alias,
+            CryptoTransactions::CustodialAcceptMany(_) => self.custodial_accept,
+            CryptoTransactions::AcceptWithReceipt(_) => self.accept_with_receipt,
+        };
+        Height(height)
+    }
+
+    /// Checks if the type of the specified transaction is active in a block
+    /// at the specified height.
+    pub fn is_active(&self, transaction: &CryptoTransactions, height: Height) -> bool {
+        height >= self.activation_height(transaction)
+    }
+}
+
+/// Checks that the type of the transaction is active in the block being created.
+fn check_activation(
+    fork: &Fork,
+    config: &Config,
+    transaction: &CryptoTransactions,
+) -> Result<(), Error> {
+    let height = CoreSchema::new(fork).height().next();
+    if config.activation_heights.is_active(transaction, height) {
+        Ok(())
+    } else {
+        Err(Error::InactiveTransaction)
+    }
+}
+
+/// Transaction logic parameterized by the service configuration.
+///
+/// `Transaction` implementations for the service transactions use [`CONFIG`]. A service
+/// created with a custom configuration binds transactions to it with
+/// [`ConfiguredTransaction`].
+///
+/// [`CONFIG`]: ::CONFIG
+/// [`ConfiguredTransaction`]: self::ConfiguredTransaction
+pub(crate) trait ConfigurableTransaction: Transaction {
+    /// Verifies the internal consistency of the transaction.
+    fn verify_with_config(&self, config: &Config) -> bool;
+
+    /// Executes the transaction.
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError>;
+}
+
+/// Implements `Transaction` for the specified types using [`CONFIG`].
+///
+/// [`CONFIG`]: ::CONFIG
+macro_rules! impl_transaction {
+    ($($name:ident),+) => {
+        $(
+            impl Transaction for $name {
+                fn verify(&self) -> bool {
+                    self.verify_with_config(&CONFIG)
+                }
+
+                fn execute(&self, fork: &mut Fork) -> Result<(), ExecutionError> {
+                    self.execute_with_config(fork, &CONFIG)
+                }
+            }
+        )+
+    };
+}
+
+impl_transaction!(
+    CreateWallet,
+    Transfer,
+    Accept,
+    PreAccept,
+    Commit,
+    DelegateAcceptance,
+    Reject,
+    RegisterAlias,
+    CustodialAcceptMany,
+    AcceptWithReceipt,
+    DelegatedAccept
+);
+
+impl ConfigurableTransaction for CreateWallet {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.create_wallet && self.verify_signature(self.key())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, _config: &Config) -> Result<(), ExecutionError> {
         let mut schema = Schema::new(fork);
         schema.create_wallet(self.key(), self)?;
         Ok(())
@@ -128,26 +542,32 @@ impl Transfer {
     }
 }
 
-impl Transaction for Transfer {
-    fn verify(&self) -> bool {
-        if CONFIG.rollback_delay_bounds.start > self.rollback_delay()
-            || CONFIG.rollback_delay_bounds.end <= self.rollback_delay()
+impl ConfigurableTransaction for Transfer {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        if !config.enabled_transactions.transfer {
+            return false;
+        }
+        if config.rollback_delay_bounds.start > self.rollback_delay()
+            || config.rollback_delay_bounds.end <= self.rollback_delay()
         {
             return false;
         }
         self.history_len() > 0
             && self.from() != self.to()
+            && self.encrypted_data().is_well_formed()
             && self.verify_signature(self.from())
             && self.verify_stateless()
     }
 
-    fn execute(&self, fork: &mut Fork) -> Result<(), ExecutionError> {
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
         let (sender, receiver) = {
             let schema = Schema::new(fork.as_ref());
             (schema.wallet(self.from()), schema.wallet(self.to()))
         };
         let sender = sender.ok_or(Error::UnregisteredSender)?;
-        let receiver = receiver.ok_or(Error::UnregisteredReceiver)?;
+        if receiver.is_none() && !(config.auto_create_receivers && is_valid_key(self.to())) {
+            Err(Error::UnregisteredReceiver)?;
+        }
 
         if sender.last_send_index() + 1 > self.history_len() {
             Err(Error::OutdatedHistory)?;
@@ -156,38 +576,56 @@ impl Transaction for Transfer {
             let schema = Schema::new(fork.as_ref());
             schema
                 .past_balance(sender.public_key(), self.history_len() - 1)
-                .ok_or_else(|| {
-                    println!(
-                        "!!! missing ref: {} / {} / len={}",
-                        self.history_len() - 1,
-                        sender.last_send_index(),
-                        sender.history_len()
-                    );
-                    Error::InvalidHistoryRef
-                })?
+                .ok_or(Error::InvalidHistoryRef)?
         };
         if !self.verify_stateful(&past_balance) {
             Err(Error::IncorrectProof)?;
         }
+        let window = config.encrypted_data_dedup_window;
+        if window > 0 {
+            let schema = Schema::new(fork.as_ref());
+            if schema.has_recent_encrypted_data(self.from(), &self.encrypted_data(), window) {
+                Err(Error::DuplicateEncryptedData)?;
+            }
+        }
 
         let mut schema = Schema::new(fork);
         schema.update_sender(&sender, &self.amount(), self);
+        let receiver = match receiver {
+            Some(receiver) => receiver,
+            None => schema.create_implicit_wallet(self.to(), self),
+        };
         schema.add_unaccepted_payment(&receiver, self);
 
         Ok(())
     }
 }
 
-impl Transaction for Accept {
-    fn verify(&self) -> bool {
-        self.verify_signature(self.receiver())
+/// Checks whether the key is a valid Ed25519 point, i.e., it can be converted
+/// into an encryption key. Small-order points are rejected, since their conversion
+/// fails in libsodium.
+fn is_valid_key(key: &PublicKey) -> bool {
+    CompressedEdwardsY::from_slice(key.as_ref())
+        .decompress()
+        .map_or(false, |point| !point.is_small_order())
+}
+
+impl ConfigurableTransaction for Accept {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.accept && self.verify_signature(self.receiver())
     }
 
-    fn execute(&self, fork: &mut Fork) -> Result<(), ExecutionError> {
+    fn execute_with_config(&self, fork: &mut Fork, _config: &Config) -> Result<(), ExecutionError> {
         let transfer = maybe_transfer(&fork, self.transfer_id()).ok_or(Error::UnknownTransfer)?;
         if transfer.to() != self.receiver() {
             Err(Error::UnauthorizedAccept)?;
         }
+        if Schema::new(fork.as_ref())
+            .pre_accepted_transfers()
+            .contains(self.transfer_id())
+        {
+            Err(Error::AlreadyPreAccepted)?;
+        }
 
         let mut schema = Schema::new(fork);
         schema.accept_payment(&transfer, self.transfer_id())?;
@@ -195,6 +633,289 @@ impl Transaction for Accept {
     }
 }
 
+impl ConfigurableTransaction for PreAccept {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.two_phase_accept && self.verify_signature(self.receiver())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(fork, config, &CryptoTransactions::PreAccept(self.clone()))?;
+        let transfer = maybe_transfer(&fork, self.transfer_id()).ok_or(Error::UnknownTransfer)?;
+        if transfer.to() != self.receiver() {
+            Err(Error::UnauthorizedAccept)?;
+        }
+
+        let mut schema = Schema::new(fork);
+        schema.pre_accept_payment(&transfer, self.transfer_id(), &self.hash())?;
+        Ok(())
+    }
+}
+
+impl ConfigurableTransaction for Reject {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.reject && self.verify_signature(self.receiver())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(fork, config, &CryptoTransactions::Reject(self.clone()))?;
+        let transfer = maybe_transfer(&fork, self.transfer_id()).ok_or(Error::UnknownTransfer)?;
+        if transfer.to() != self.receiver() {
+            Err(Error::UnauthorizedAccept)?;
+        }
+        // The receiver has committed to accept a pre-accepted transfer, so it may only
+        // be committed by the sender or rolled back.
+        if Schema::new(fork.as_ref())
+            .pre_accepted_transfers()
+            .contains(self.transfer_id())
+        {
+            Err(Error::AlreadyPreAccepted)?;
+        }
+
+        let mut schema = Schema::new(fork);
+        schema.reject_payment(&transfer, self.transfer_id())?;
+        Ok(())
+    }
+}
+
+impl ConfigurableTransaction for DelegateAcceptance {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.delegate_acceptance
+            && self.owner() != self.delegate()
+            && self.verify_signature(self.owner())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(
+            fork,
+            config,
+            &CryptoTransactions::DelegateAcceptance(self.clone()),
+        )?;
+        let mut schema = Schema::new(fork);
+        if schema.wallet(self.owner()).is_none() {
+            Err(Error::UnregisteredOwner)?;
+        }
+        if self.revoke() {
+            schema.remove_acceptance_delegate(self.owner(), self.delegate())?;
+        } else {
+            schema.add_acceptance_delegate(self.owner(), self.delegate())?;
+        }
+        Ok(())
+    }
+}
+
+impl ConfigurableTransaction for Commit {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.two_phase_accept && self.verify_signature(self.sender())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(fork, config, &CryptoTransactions::Commit(self.clone()))?;
+        let transfer = maybe_transfer(&fork, self.transfer_id()).ok_or(Error::UnknownTransfer)?;
+        if transfer.from() != self.sender() {
+            Err(Error::UnauthorizedCommit)?;
+        }
+
+        let mut schema = Schema::new(fork);
+        schema.commit_payment(&transfer, self.transfer_id())?;
+        Ok(())
+    }
+}
+
+impl ConfigurableTransaction for RegisterAlias {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.register_alias
+            && is_valid_alias(self.alias())
+            && self.verify_signature(self.owner())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(
+            fork,
+            config,
+            &CryptoTransactions::RegisterAlias(self.clone()),
+        )?;
+        let mut schema = Schema::new(fork);
+        if schema.wallet(self.owner()).is_none() {
+            Err(Error::UnregisteredOwner)?;
+        }
+        schema.register_alias(self.owner(), self.alias())?;
+        Ok(())
+    }
+}
+
+impl ConfigurableTransaction for CustodialAcceptMany {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        if !config.enabled_transactions.custodial_accept || !self.verify_signature(self.custodian())
+        {
+            return false;
+        }
+        let accepts = match self.parse_accepts() {
+            Some(accepts) => accepts,
+            None => return false,
+        };
+        !accepts.is_empty()
+            && accepts.len() <= config.max_custodial_accepts as usize
+            && accepts
+                .into_iter()
+                .all(|accept| accept.into_configurable().verify_with_config(config))
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(
+            fork,
+            config,
+            &CryptoTransactions::CustodialAcceptMany(self.clone()),
+        )?;
+        // `verify()` guarantees that all accepts are parsed successfully.
+        let accepts = self.parse_accepts().unwrap_or_default();
+        for accept in accepts {
+            accept
+                .into_configurable()
+                .execute_with_config(fork, config)?;
+        }
+        Ok(())
+    }
+}
+
+impl ConfigurableTransaction for AcceptWithReceipt {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.accept_with_receipt
+            && self.receipt().is_receipt_within(config.max_receipt_len)
+            && self.verify_signature(self.receiver())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(
+            fork,
+            config,
+            &CryptoTransactions::AcceptWithReceipt(self.clone()),
+        )?;
+        let transfer = maybe_transfer(&fork, self.transfer_id()).ok_or(Error::UnknownTransfer)?;
+        if transfer.to() != self.receiver() {
+            Err(Error::UnauthorizedAccept)?;
+        }
+        if Schema::new(fork.as_ref())
+            .pre_accepted_transfers()
+            .contains(self.transfer_id())
+        {
+            Err(Error::AlreadyPreAccepted)?;
+        }
+
+        let mut schema = Schema::new(fork);
+        schema.accept_payment(&transfer, self.transfer_id())?;
+        schema.add_receipt(self.transfer_id(), self.receipt());
+        Ok(())
+    }
+}
+
+impl ConfigurableTransaction for DelegatedAccept {
+    fn verify_with_config(&self, config: &Config) -> bool {
+        config.enabled_transactions.delegate_acceptance
+            && self.receiver() != self.delegate()
+            && self.verify_signature(self.delegate())
+    }
+
+    fn execute_with_config(&self, fork: &mut Fork, config: &Config) -> Result<(), ExecutionError> {
+        check_activation(
+            fork,
+            config,
+            &CryptoTransactions::DelegatedAccept(self.clone()),
+        )?;
+        let transfer = maybe_transfer(&fork, self.transfer_id()).ok_or(Error::UnknownTransfer)?;
+        if transfer.to() != self.receiver() {
+            Err(Error::UnauthorizedAccept)?;
+        }
+        let schema = Schema::new(fork.as_ref());
+        if !schema.is_acceptance_delegate(self.receiver(), self.delegate()) {
+            Err(Error::UnauthorizedAccept)?;
+        }
+        if schema.pre_accepted_transfers().contains(self.transfer_id()) {
+            Err(Error::AlreadyPreAccepted)?;
+        }
+
+        let mut schema = Schema::new(fork);
+        schema.accept_payment(&transfer, self.transfer_id())?;
+        Ok(())
+    }
+}
+
+impl CryptoTransactions {
+    /// Converts the transaction into a trait object with configurable logic.
+    pub(crate) fn into_configurable(self) -> Box<dyn ConfigurableTransaction> {
+        match self {
+            CryptoTransactions::CreateWallet(tx) => Box::new(tx),
+            CryptoTransactions::Transfer(tx) => Box::new(tx),
+            CryptoTransactions::Accept(tx) => Box::new(tx),
+            CryptoTransactions::PreAccept(tx) => Box::new(tx),
+            CryptoTransactions::Commit(tx) => Box::new(tx),
+            CryptoTransactions::DelegateAcceptance(tx) => Box::new(tx),
+            CryptoTransactions::Reject(tx) => Box::new(tx),
+            CryptoTransactions::RegisterAlias(tx) => Box::new(tx),
+            CryptoTransactions::CustodialAcceptMany(tx) => Box::new(tx),
+            CryptoTransactions::AcceptWithReceipt(tx) => Box::new(tx),
+            CryptoTransactions::DelegatedAccept(tx) => Box::new(tx),
+        }
+    }
+}
+
+/// Service transaction bound to a specific service configuration.
+///
+/// Used by services created with [`Service::with_config()`].
+///
+/// [`Service::with_config()`]: ::Service::with_config()
+#[derive(Debug)]
+pub(crate) struct ConfiguredTransaction {
+    transaction: Box<dyn ConfigurableTransaction>,
+    config: Arc<Config>,
+}
+
+impl ConfiguredTransaction {
+    pub(crate) fn new(transaction: CryptoTransactions, config: Arc<Config>) -> Self {
+        ConfiguredTransaction {
+            transaction: transaction.into_configurable(),
+            config,
+        }
+    }
+}
+
+impl Message for ConfiguredTransaction {
+    // Services with a custom configuration wrap transactions in `tx_from_raw()`,
+    // so a transaction parsed here is bound to the default configuration.
+    fn from_raw(raw: RawMessage) -> Result<Self, EncodingError> {
+        let transaction = CryptoTransactions::tx_from_raw(raw)?;
+        Ok(ConfiguredTransaction::new(transaction, Arc::new(CONFIG)))
+    }
+
+    fn raw(&self) -> &RawMessage {
+        self.transaction.raw()
+    }
+}
+
+impl ExonumJson for ConfiguredTransaction {
+    fn deserialize_field<B: WriteBufferWrapper>(
+        _value: &Value,
+        _buffer: &mut B,
+        _from: u32,
+        _to: u32,
+    ) -> Result<(), Box<dyn StdError>> {
+        Err("configured transactions cannot be embedded into other messages".into())
+    }
+
+    fn serialize_field(&self) -> Result<Value, Box<dyn StdError + Send + Sync>> {
+        self.transaction.serialize_field()
+    }
+}
+
+impl Transaction for ConfiguredTransaction {
+    fn verify(&self) -> bool {
+        self.transaction.verify_with_config(&self.config)
+    }
+
+    fn execute(&self, fork: &mut Fork) -> Result<(), ExecutionError> {
+        self.transaction.execute_with_config(fork, &self.config)
+    }
+}
+
 /// Errors that can occur during transaction processing.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Fail)]
 #[repr(u8)]
@@ -239,19 +960,99 @@ pub enum Error {
 
     /// An `Accept` transaction references an unknown transfer.
     ///
-    /// Can occur in [`Accept`](self::Accept).
+    /// Can occur in [`Accept`](self::Accept), [`PreAccept`](self::PreAccept),
+    /// [`Commit`](self::Commit), [`Reject`](self::Reject),
+    /// [`CustodialAcceptMany`](self::CustodialAcceptMany),
+    /// [`AcceptWithReceipt`](self::AcceptWithReceipt)
+    /// and [`DelegatedAccept`](self::DelegatedAccept).
     #[fail(display = "an `Accept` transaction references an unknown transfer")]
     UnknownTransfer = 6,
 
     /// The author of an `Accept` transaction differs from the receiver of the referenced
-    /// transfer.
+    /// transfer and is not an acceptance delegate of the receiver.
     ///
-    /// Can occur in [`Accept`](self::Accept).
+    /// Can occur in [`Accept`](self::Accept), [`PreAccept`](self::PreAccept),
+    /// [`Reject`](self::Reject), [`CustodialAcceptMany`](self::CustodialAcceptMany),
+    /// [`AcceptWithReceipt`](self::AcceptWithReceipt)
+    /// and [`DelegatedAccept`](self::DelegatedAccept).
     #[fail(
         display = "the author of an `Accept` transaction differs from the receiver \
                    of the referenced transfer"
     )]
     UnauthorizedAccept = 7,
+
+    /// The referenced transfer has already been pre-accepted by the receiver.
+    ///
+    /// Can occur in [`Accept`](self::Accept), [`PreAccept`](self::PreAccept),
+    /// [`Reject`](self::Reject), [`CustodialAcceptMany`](self::CustodialAcceptMany),
+    /// [`AcceptWithReceipt`](self::AcceptWithReceipt)
+    /// and [`DelegatedAccept`](self::DelegatedAccept).
+    #[fail(display = "the referenced transfer has already been pre-accepted by the receiver")]
+    AlreadyPreAccepted = 8,
+
+    /// A `Commit` transaction references a transfer that is not pre-accepted.
+    ///
+    /// Can occur in [`Commit`](self::Commit).
+    #[fail(display = "a `Commit` transaction references a transfer that is not pre-accepted")]
+    NotPreAccepted = 9,
+
+    /// The author of a `Commit` transaction differs from the sender of the referenced
+    /// transfer.
+    ///
+    /// Can occur in [`Commit`](self::Commit).
+    #[fail(
+        display = "the author of a `Commit` transaction differs from the sender \
+                   of the referenced transfer"
+    )]
+    UnauthorizedCommit = 10,
+
+    /// The owner of the wallet is not registered.
+    ///
+    /// Can occur in [`DelegateAcceptance`](self::DelegateAcceptance)
+    /// and [`RegisterAlias`](self::RegisterAlias).
+    #[fail(display = "the owner of the wallet is not registered")]
+    UnregisteredOwner = 11,
+
+    /// The key is already an acceptance delegate for the wallet.
+    ///
+    /// Can occur in [`DelegateAcceptance`](self::DelegateAcceptance).
+    #[fail(display = "the key is already an acceptance delegate for the wallet")]
+    DelegateExists = 12,
+
+    /// The key is not an acceptance delegate for the wallet.
+    ///
+    /// Can occur in [`DelegateAcceptance`](self::DelegateAcceptance).
+    #[fail(display = "the key is not an acceptance delegate for the wallet")]
+    UnknownDelegate = 13,
+
+    /// The alias is already registered.
+    ///
+    /// Can occur in [`RegisterAlias`](self::RegisterAlias).
+    #[fail(display = "the alias is already registered")]
+    AliasTaken = 14,
+
+    /// The wallet has already registered an alias.
+    ///
+    /// Can occur in [`RegisterAlias`](self::RegisterAlias).
+    #[fail(display = "the wallet has already registered an alias")]
+    AliasAlreadyRegistered = 15,
+
+    /// The encrypted data of a transfer is identical to that of a recent transfer
+    /// from the same sender.
+    ///
+    /// Can occur in [`Transfer`](self::Transfer) if enabled with
+    /// [`Config::encrypted_data_dedup_window`](::Config::encrypted_data_dedup_window).
+    #[fail(
+        display = "the encrypted data of a transfer is identical to that of a recent transfer \
+                   from the same sender"
+    )]
+    DuplicateEncryptedData = 16,
+
+    /// The transaction type is not active at the current blockchain height.
+    ///
+    /// Can occur in transactions gated by [`ActivationHeights`](self::ActivationHeights).
+    #[fail(display = "the transaction type is not active at the current blockchain height")]
+    InactiveTransaction = 17,
 }
 
 impl From<Error> for ExecutionError {
@@ -15,18 +15,38 @@
 //! Tests for HTTP API of the service.
 
 extern crate exonum;
+extern crate exonum_sodiumoxide as sodiumoxide;
 #[macro_use]
 extern crate exonum_testkit;
 extern crate private_currency;
 
-use exonum::crypto::{CryptoHash, PublicKey};
+use exonum::{
+    blockchain::{Transaction, TransactionSet},
+    crypto::{self, x25519, CryptoHash, Hash, PublicKey, SecretKey},
+    encoding::serialize::{decode_hex, encode_hex},
+    helpers::Height,
+    messages::{Message, RawMessage},
+};
+use sodiumoxide::crypto::box_;
 use exonum_testkit::{ApiKind, TestKit, TestKitBuilder};
 
 use std::{collections::HashSet, iter::FromIterator};
 
 use private_currency::{
-    api::{CheckedWalletProof, FullEvent, TrustAnchor, WalletProof, WalletQuery},
-    SecretState, Service as Currency,
+    api::{
+        AliasProof, AliasQuery, BlockQuery, CheckedWalletProof, EncryptionKey, EncryptionKeyQuery,
+        FullEvent, PendingTransfer, PendingTransfersQuery, SigningPreimageQuery,
+        StateHashComponents, TransferQuery, TransferStage, TransferStatus, TrustAnchor,
+        WalletProof, WalletQuery, WalletsProof, WalletsQuery,
+    },
+    client::{create_transfer_to_alias, AliasTransferError},
+    replay_blocks,
+    storage::{
+        ACCEPTANCE_DELEGATES_TABLE_INDEX, ALIASES_TABLE_INDEX, PRE_ACCEPTED_TABLE_INDEX,
+        RECEIPTS_TABLE_INDEX, WALLETS_TABLE_INDEX,
+    },
+    transactions::{CustodialAcceptMany, DelegatedAccept},
+    Config, ExportedBlock, SecretState, Service as Currency, Transactions, CONFIG,
 };
 
 fn create_testkit() -> TestKit {
@@ -35,14 +55,18 @@ fn create_testkit() -> TestKit {
         .create()
 }
 
-fn wallet(testkit: &TestKit, key: PublicKey, start_history_at: u64) -> CheckedWalletProof {
-    let trust_anchor = TrustAnchor::new(
+fn trust_anchor(testkit: &TestKit) -> TrustAnchor {
+    TrustAnchor::new(
         testkit
             .network()
             .validators()
             .iter()
             .map(|node| node.public_keys().consensus_key),
-    );
+    )
+}
+
+fn wallet(testkit: &TestKit, key: PublicKey, start_history_at: u64) -> CheckedWalletProof {
+    let trust_anchor = trust_anchor(testkit);
 
     let query = WalletQuery {
         key,
@@ -57,6 +81,15 @@ fn wallet(testkit: &TestKit, key: PublicKey, start_history_at: u64) -> CheckedWa
     wallet_proof.check(&trust_anchor, &query).unwrap()
 }
 
+fn transfer_status(testkit: &TestKit, id: Hash) -> TransferStatus {
+    testkit
+        .api()
+        .public(ApiKind::Service("private_currency"))
+        .query(&TransferQuery { id })
+        .get("v1/transfer")
+        .unwrap()
+}
+
 #[test]
 fn wallet_api() {
     let mut testkit = create_testkit();
@@ -123,3 +156,531 @@ fn wallet_api() {
         alice_sec.to_public()
     );
 }
+
+#[test]
+fn transfer_status_api() {
+    let mut testkit = create_testkit();
+
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 20);
+    testkit.create_block_with_transaction(transfer.clone());
+    let inclusion_height = testkit.height();
+
+    let status = transfer_status(&testkit, transfer.hash());
+    assert_eq!(status.height, inclusion_height);
+    assert_eq!(status.confirmations, 0);
+    assert_eq!(status.finality_depth, CONFIG.finality_depth);
+    assert_eq!(status.stage, TransferStage::Pending);
+    assert!(!status.is_final);
+
+    let accept = bob_sec
+        .verify_transfer(&transfer)
+        .expect("verified transfer")
+        .accept;
+    testkit.create_block_with_transaction(accept);
+    let status = transfer_status(&testkit, transfer.hash());
+    assert_eq!(status.confirmations, 1);
+    assert_eq!(status.stage, TransferStage::Accepted);
+    assert!(!status.is_final);
+
+    let final_height = Height(inclusion_height.0 + CONFIG.finality_depth);
+    testkit.create_blocks_until(final_height.previous());
+    let status = transfer_status(&testkit, transfer.hash());
+    assert_eq!(status.confirmations, CONFIG.finality_depth - 1);
+    assert!(!status.is_final);
+
+    testkit.create_block();
+    let status = transfer_status(&testkit, transfer.hash());
+    assert_eq!(status.height, inclusion_height);
+    assert_eq!(status.confirmations, CONFIG.finality_depth);
+    assert!(status.is_final);
+}
+
+#[test]
+fn pending_transfers_api() {
+    fn pending_transfers(testkit: &TestKit, key: PublicKey) -> Vec<PendingTransfer> {
+        testkit
+            .api()
+            .public(ApiKind::Service("private_currency"))
+            .query(&PendingTransfersQuery { key })
+            .get("v1/wallet/pending-transfers")
+            .unwrap()
+    }
+
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let carol_sec = SecretState::with_random_keypair();
+    let carol_pk = *carol_sec.public_key();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+        carol_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+    assert!(pending_transfers(&testkit, carol_pk).is_empty());
+
+    let long_transfer = alice_sec.create_transfer(1_000, &carol_pk, 20);
+    let short_transfer = bob_sec.create_transfer(1_000, &carol_pk, 5);
+    testkit.create_block_with_transactions(txvec![
+        long_transfer.clone(),
+        short_transfer.clone(),
+    ]);
+    testkit.create_block();
+
+    // Transfers at height 2 are rolled back in the blocks at heights 8 and 23.
+    assert_eq!(
+        pending_transfers(&testkit, carol_pk),
+        vec![
+            PendingTransfer {
+                id: short_transfer.hash(),
+                rollback_height: Height(8),
+                remaining_blocks: 5,
+            },
+            PendingTransfer {
+                id: long_transfer.hash(),
+                rollback_height: Height(23),
+                remaining_blocks: 20,
+            },
+        ]
+    );
+
+    // Transfers disappear from the list once they are rolled back.
+    testkit.create_blocks_until(Height(8));
+    let pending = pending_transfers(&testkit, carol_pk);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, long_transfer.hash());
+    assert_eq!(pending[0].remaining_blocks, 15);
+
+    // Unknown wallets are reported as missing.
+    let (unknown_pk, _) = crypto::gen_keypair();
+    let response: Result<Vec<PendingTransfer>, _> = testkit
+        .api()
+        .public(ApiKind::Service("private_currency"))
+        .query(&PendingTransfersQuery { key: unknown_pk })
+        .get("v1/wallet/pending-transfers");
+    assert!(response.is_err());
+}
+
+#[test]
+fn encryption_key_api() {
+    let mut testkit = create_testkit();
+
+    let alice_sec = SecretState::with_random_keypair();
+    let (bob_pk, bob_sk) = crypto::gen_keypair();
+    let bob_sec = SecretState::from_keypair(bob_pk, bob_sk.clone());
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+
+    let api = testkit.api();
+    let response: EncryptionKey = api
+        .public(ApiKind::Service("private_currency"))
+        .query(&EncryptionKeyQuery { key: bob_pk })
+        .get("v1/wallet/encryption-key")
+        .unwrap();
+    assert_eq!(response.key, bob_pk);
+    assert_eq!(response.encryption_key, bob_sec.to_public().encryption_key());
+
+    // A third party encrypts a message with the key; Bob should be able to decrypt it.
+    const MSG: &[u8] = b"Hello, Bob!";
+    let (sender_pk, sender_sk) = box_::gen_keypair();
+    let nonce = box_::gen_nonce();
+    let sealed = box_::seal(MSG, &nonce, &response.encryption_key, &sender_sk);
+    let (_, bob_enc_sk) = x25519::into_x25519_keypair(bob_pk, bob_sk).unwrap();
+    let bob_enc_sk = box_::SecretKey::from_slice(bob_enc_sk.as_ref()).unwrap();
+    assert_eq!(
+        box_::open(&sealed, &nonce, &sender_pk, &bob_enc_sk),
+        Ok(MSG.to_vec())
+    );
+
+    // The key is not available for unregistered wallets.
+    let (unknown_pk, _) = crypto::gen_keypair();
+    let response = api
+        .public(ApiKind::Service("private_currency"))
+        .query(&EncryptionKeyQuery { key: unknown_pk })
+        .get::<EncryptionKey>("v1/wallet/encryption-key");
+    assert!(response.is_err());
+}
+
+#[test]
+fn bulk_wallet_proofs_api() {
+    const WALLET_COUNT: usize = 50;
+
+    let mut testkit = create_testkit();
+    let mut secrets: Vec<_> = (0..WALLET_COUNT)
+        .map(|_| SecretState::with_random_keypair())
+        .collect();
+    // Register all wallets except for the last 5 ones.
+    let registered = WALLET_COUNT - 5;
+    testkit.create_block_with_transactions(
+        secrets[..registered]
+            .iter()
+            .map(|sec| Box::new(sec.create_wallet()) as Box<dyn Transaction>),
+    );
+
+    let query = WalletsQuery {
+        keys: secrets.iter().map(|sec| *sec.public_key()).collect(),
+    };
+    let proof: WalletsProof = testkit
+        .api()
+        .public(ApiKind::Service("private_currency"))
+        .query(&query)
+        .post("v1/wallets/proofs")
+        .unwrap();
+    let checked = proof.check(&trust_anchor(&testkit), &query).unwrap();
+    assert_eq!(checked.block.height(), testkit.height());
+    assert_eq!(checked.wallets.len(), WALLET_COUNT);
+
+    for (i, (sec, wallet)) in secrets.iter_mut().zip(&checked.wallets).enumerate() {
+        if i < registered {
+            sec.initialize();
+            let wallet = wallet.as_ref().expect("wallet");
+            assert_eq!(wallet.info(), sec.to_public());
+        } else {
+            assert!(wallet.is_none());
+        }
+    }
+
+    // The proof cannot be used to authenticate keys not present in the query.
+    let other_query = WalletsQuery {
+        keys: vec![crypto::gen_keypair().0],
+    };
+    assert!(proof.check(&trust_anchor(&testkit), &other_query).is_err());
+}
+
+#[test]
+fn state_hash_components_api() {
+    fn all_components(testkit: &TestKit) -> StateHashComponents {
+        testkit
+            .api()
+            .private(ApiKind::Service("private_currency"))
+            .get("v1/debug/state-hash")
+            .unwrap()
+    }
+
+    fn components(testkit: &TestKit) -> Vec<(String, Hash)> {
+        all_components(testkit).state_hash
+    }
+
+    let mut testkit = create_testkit();
+    let initial = components(&testkit);
+    let names: Vec<_> = initial.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names.len(), 5);
+    assert_eq!(names[WALLETS_TABLE_INDEX], "private_currency.wallets");
+    assert_eq!(
+        names[PRE_ACCEPTED_TABLE_INDEX],
+        "private_currency.pre_accepted"
+    );
+    assert_eq!(
+        names[ACCEPTANCE_DELEGATES_TABLE_INDEX],
+        "private_currency.acceptance_delegates"
+    );
+    assert_eq!(names[ALIASES_TABLE_INDEX], "private_currency.aliases");
+    assert_eq!(names[RECEIPTS_TABLE_INDEX], "private_currency.receipts");
+
+    // Components are stable if the state does not change.
+    testkit.create_block();
+    assert_eq!(components(&testkit), initial);
+
+    // Only the wallets component changes when a wallet is created.
+    let mut alice_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transaction(alice_sec.create_wallet());
+    let with_wallet = components(&testkit);
+    assert_ne!(with_wallet[0], initial[0]);
+    assert_eq!(with_wallet[1..], initial[1..]);
+
+    // Only the delegates component changes when an acceptance delegate is added.
+    let (delegate, _) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(alice_sec.delegate_acceptance(&delegate));
+    let with_delegate = components(&testkit);
+    assert_eq!(with_delegate[..2], with_wallet[..2]);
+    assert_ne!(with_delegate[2], with_wallet[2]);
+    assert_eq!(with_delegate[3], with_wallet[3]);
+
+    // Only the aliases component changes when an alias is registered.
+    testkit.create_block_with_transaction(alice_sec.register_alias("alice"));
+    let with_alias = components(&testkit);
+    assert_eq!(with_alias[..3], with_delegate[..3]);
+    assert_ne!(with_alias[3], with_delegate[3]);
+    assert_eq!(with_alias[4], with_delegate[4]);
+
+    // Auxiliary digests cover unaccepted transfers and the rollback queue.
+    alice_sec.initialize();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transaction(bob_sec.create_wallet());
+    let before_transfer = all_components(&testkit).auxiliary;
+    let names: Vec<_> = before_transfer
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "private_currency.unaccepted_payments",
+            "private_currency.rollback_by_height",
+        ]
+    );
+
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    testkit.create_block_with_transaction(transfer);
+    let after_transfer = all_components(&testkit).auxiliary;
+    assert_ne!(after_transfer[0], before_transfer[0]);
+    assert_ne!(after_transfer[1], before_transfer[1]);
+
+    // Once the transfer is rolled back, the digests return to their previous values.
+    testkit.create_blocks_until(Height(testkit.height().0 + 11));
+    assert_eq!(all_components(&testkit).auxiliary, before_transfer);
+}
+
+#[test]
+fn auxiliary_hash_components_respect_service_config() {
+    let config = Config {
+        rollback_delay_bounds: 5..2_000,
+        ..CONFIG
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Currency::with_config(config.clone()))
+        .create();
+    let auxiliary = |testkit: &TestKit| {
+        testkit
+            .api()
+            .private(ApiKind::Service("private_currency"))
+            .get::<StateHashComponents>("v1/debug/state-hash")
+            .unwrap()
+            .auxiliary
+    };
+
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+    let before_transfer = auxiliary(&testkit);
+
+    // The transfer is scheduled for rollback beyond the maximum delay of the default config.
+    let transfer =
+        alice_sec.create_transfer_with_config(1_000, bob_sec.public_key(), 1_500, &config);
+    testkit.create_block_with_transaction(transfer);
+    let after_transfer = auxiliary(&testkit);
+    assert_ne!(after_transfer[1], before_transfer[1]);
+}
+
+#[test]
+fn blocks_exported_via_api_can_be_replayed() {
+    fn latest_block(testkit: &TestKit) -> ExportedBlock {
+        testkit
+            .api()
+            .private(ApiKind::Service("private_currency"))
+            .get("v1/debug/latest-block")
+            .unwrap()
+    }
+
+    fn block(testkit: &TestKit, height: Height) -> ExportedBlock {
+        testkit
+            .api()
+            .private(ApiKind::Service("private_currency"))
+            .query(&BlockQuery { height })
+            .get("v1/debug/block")
+            .unwrap()
+    }
+
+    let mut testkit = create_testkit();
+    // The state hash in block headers depends on the genesis configuration, so the blocks
+    // are replayed on the same testkit after rolling it back to the genesis block.
+    testkit.checkpoint();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    testkit.create_block_with_transaction(transfer);
+    let latest = latest_block(&testkit);
+    assert_eq!(latest.height(), Height(2));
+    assert_eq!(latest.transactions.len(), 1);
+    assert_eq!(block(&testkit, Height(2)), latest);
+
+    // Earlier blocks are exported without the service state hash.
+    let exported = vec![block(&testkit, Height(1)), latest];
+    assert_eq!(exported[0].transactions.len(), 2);
+    assert!(exported[0].state_hash.is_none());
+    assert!(exported[1].state_hash.is_some());
+
+    testkit.rollback();
+    replay_blocks(testkit.blockchain_mut(), &exported).unwrap();
+    assert_eq!(latest_block(&testkit), exported[1]);
+}
+
+#[test]
+fn config_api() {
+    let testkit = create_testkit();
+    let config: Config = testkit
+        .api()
+        .public(ApiKind::Service("private_currency"))
+        .get("v1/config")
+        .unwrap();
+    assert_eq!(config, CONFIG);
+
+    let delay = config.default_rollback_delay;
+    assert!(config.rollback_delay_bounds.start <= delay);
+    assert!(delay < config.rollback_delay_bounds.end);
+
+    // A service with a custom configuration reports it.
+    let custom_config = Config {
+        default_rollback_delay: 20,
+        ..CONFIG
+    };
+    let testkit = TestKitBuilder::validator()
+        .with_service(Currency::with_config(custom_config.clone()))
+        .create();
+    let config: Config = testkit
+        .api()
+        .public(ApiKind::Service("private_currency"))
+        .get("v1/config")
+        .unwrap();
+    assert_eq!(config, custom_config);
+}
+
+#[test]
+fn alias_api() {
+    fn alias_proof(testkit: &TestKit, query: &AliasQuery) -> AliasProof {
+        testkit
+            .api()
+            .public(ApiKind::Service("private_currency"))
+            .query(query)
+            .get("v1/alias")
+            .unwrap()
+    }
+
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    testkit.create_block_with_transaction(bob_sec.register_alias("bob"));
+
+    let query = AliasQuery {
+        alias: "bob".to_owned(),
+    };
+    let proof = alias_proof(&testkit, &query);
+    let checked = proof.check(&trust_anchor(&testkit), &query).unwrap();
+    assert_eq!(checked.block.height(), testkit.height());
+    assert_eq!(checked.key, Some(*bob_sec.public_key()));
+
+    // The proof cannot be used for another alias.
+    let other_query = AliasQuery {
+        alias: "carol".to_owned(),
+    };
+    assert!(proof.check(&trust_anchor(&testkit), &other_query).is_err());
+
+    // Send a transfer to the alias.
+    let transfer = create_transfer_to_alias(
+        &alice_sec,
+        1_000,
+        &query,
+        &proof,
+        &trust_anchor(&testkit),
+        10,
+    ).unwrap();
+    assert_eq!(transfer.to(), bob_sec.public_key());
+    let block = testkit.create_block_with_transaction(transfer);
+    assert!(block[0].status().is_ok());
+
+    // Unregistered aliases are proven to be absent.
+    let proof = alias_proof(&testkit, &other_query);
+    let checked = proof.check(&trust_anchor(&testkit), &other_query).unwrap();
+    assert_eq!(checked.key, None);
+    let err = create_transfer_to_alias(
+        &alice_sec,
+        1_000,
+        &other_query,
+        &proof,
+        &trust_anchor(&testkit),
+        10,
+    ).unwrap_err();
+    match err {
+        AliasTransferError::UnknownAlias => {}
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn signing_preimage_api() {
+    fn signing_preimage(testkit: &TestKit, unsigned_tx: &[u8]) -> Result<Vec<u8>, ()> {
+        let query = SigningPreimageQuery {
+            unsigned_tx: encode_hex(unsigned_tx),
+        };
+        let preimage: String = testkit
+            .api()
+            .private(ApiKind::Service("private_currency"))
+            .query(&query)
+            .get("v1/debug/signing-preimage")
+            .map_err(drop)?;
+        Ok(decode_hex(&preimage).unwrap())
+    }
+
+    let testkit = create_testkit();
+    let (alice_pk, alice_sk) = crypto::gen_keypair();
+    let (bob_pk, bob_sk) = crypto::gen_keypair();
+    let (delegate_pk, delegate_sk) = crypto::gen_keypair();
+    let mut alice_sec = SecretState::from_keypair(alice_pk, alice_sk.clone());
+    let bob_sec = SecretState::from_keypair(bob_pk, bob_sk.clone());
+    alice_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    let accept = DelegatedAccept::new(&bob_pk, &transfer.hash(), &delegate_pk, &delegate_sk);
+    let (custodian_pk, custodian_sk) = crypto::gen_keypair();
+    let custodial_accept =
+        CustodialAcceptMany::new(&custodian_pk, vec![accept.raw().clone()], &custodian_sk);
+    let signed_txs: Vec<(RawMessage, &SecretKey)> = vec![
+        (alice_sec.create_wallet().raw().clone(), &alice_sk),
+        (transfer.raw().clone(), &alice_sk),
+        (accept.raw().clone(), &delegate_sk),
+        (bob_sec.create_pre_accept(&transfer).raw().clone(), &bob_sk),
+        (bob_sec.create_reject(&transfer).raw().clone(), &bob_sk),
+        (
+            bob_sec.delegate_acceptance(&delegate_pk).raw().clone(),
+            &bob_sk,
+        ),
+        (alice_sec.create_commit(&transfer).raw().clone(), &alice_sk),
+        (alice_sec.register_alias("alice").raw().clone(), &alice_sk),
+        (custodial_accept.raw().clone(), &custodian_sk),
+    ];
+
+    for (raw, secret_key) in signed_txs {
+        let signed: &[u8] = raw.as_ref();
+        let body = &signed[..signed.len() - crypto::SIGNATURE_LENGTH];
+        let mut unsigned = body.to_vec();
+        // Clients may not know how to fill in the payload length; the endpoint does it for them.
+        unsigned[6..10].copy_from_slice(&[0; 4]);
+        let preimage = signing_preimage(&testkit, &unsigned).expect("signing preimage");
+        assert_eq!(preimage[..], body[..]);
+
+        let signature = crypto::sign(&preimage, secret_key);
+        let mut resigned = preimage;
+        resigned.extend_from_slice(signature.as_ref());
+        let tx = Transactions::tx_from_raw(RawMessage::from_vec(resigned)).unwrap();
+        let tx: Box<dyn Transaction> = tx.into();
+        assert!(tx.verify());
+        assert_eq!(tx.hash(), raw.hash());
+    }
+
+    // Malformed transactions are rejected.
+    assert!(signing_preimage(&testkit, &[0; 5]).is_err());
+    let create_wallet = alice_sec.create_wallet();
+    let signed: &[u8] = create_wallet.raw().as_ref();
+    let truncated = &signed[..signed.len() - crypto::SIGNATURE_LENGTH - 1];
+    assert!(signing_preimage(&testkit, truncated).is_err());
+}
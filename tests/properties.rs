@@ -0,0 +1,84 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Property tests based on the strategies from the `testing` module. Run with
+//!
+//! ```shell
+//! cargo test --features testing --test properties
+//! ```
+
+extern crate exonum;
+extern crate private_currency;
+#[macro_use]
+extern crate proptest;
+
+use exonum::blockchain::Transaction;
+use private_currency::{
+    crypto::SimpleRangeProof,
+    testing::{commitments, range_proofs, transfers},
+};
+use proptest::prelude::*;
+
+/// Creating range proofs is expensive, so tests involving them use fewer cases.
+const EXPENSIVE_CASES: u32 = 16;
+
+proptest! {
+    #[test]
+    fn commitments_are_homomorphic(
+        (c1, o1) in commitments(0..=u64::max_value() / 2),
+        (c2, o2) in commitments(0..=u64::max_value() / 2)
+    ) {
+        prop_assert!(c1.verify(&o1));
+        prop_assert!((&c1 + &c2).verify(&(o1.clone() + o2.clone())));
+        prop_assert!((&(&c1 + &c2) - &c2).verify(&o1));
+        prop_assert_eq!(&(&c1 + &c2) - &c1, c2);
+    }
+
+    #[test]
+    fn commitments_do_not_verify_for_other_openings(
+        (c1, o1) in commitments(any::<u64>()),
+        (c2, o2) in commitments(any::<u64>())
+    ) {
+        prop_assume!(o1 != o2);
+        prop_assert!(!c1.verify(&o2));
+        prop_assert!(!c2.verify(&o1));
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(EXPENSIVE_CASES))]
+
+    #[test]
+    fn range_proofs_verify((commitment, _, proof) in range_proofs(any::<u64>())) {
+        prop_assert!(proof.verify(&commitment));
+        let restored = SimpleRangeProof::from_bytes(&proof.to_bytes()).unwrap();
+        prop_assert!(restored.verify(&commitment));
+    }
+
+    #[test]
+    fn range_proofs_are_bound_to_commitment(
+        (commitment, _, proof) in range_proofs(any::<u64>()),
+        (other_commitment, _) in commitments(any::<u64>())
+    ) {
+        prop_assume!(commitment != other_commitment);
+        prop_assert!(!proof.verify(&other_commitment));
+    }
+
+    #[test]
+    fn generated_transfers_are_valid((sender, transfer) in transfers()) {
+        prop_assert_eq!(transfer.from(), sender.public_key());
+        prop_assert_ne!(transfer.to(), sender.public_key());
+        prop_assert!(transfer.verify());
+    }
+}
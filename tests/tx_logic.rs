@@ -14,22 +14,33 @@
 
 //! Tests for transaction logic of the service.
 
+extern crate curve25519_dalek;
 extern crate exonum;
 #[macro_use]
 extern crate exonum_testkit;
 extern crate private_currency;
 
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use exonum::{
-    blockchain::TransactionErrorType,
-    crypto::{self, CryptoHash, Hash},
+    blockchain::{Block, Schema as CoreSchema, Transaction, TransactionErrorType},
+    crypto::{self, CryptoHash, Hash, PublicKey},
     helpers::Height,
+    messages::Message,
+    storage::{Fork, KeySetIndex},
 };
-use exonum_testkit::{TestKit, TestKitBuilder};
+use exonum_testkit::{ApiKind, TestKit, TestKitBuilder};
 use private_currency::{
+    api::{TransferQuery, TransferStage, TransferStatus},
+    client::{check_incoming_transfer, is_accepted_balance, IncomingTransfer},
     crypto::Opening,
-    storage::{Event, Schema},
-    transactions::{Accept, Error},
-    SecretState, Service as Currency, CONFIG,
+    replay_blocks,
+    storage::{Event, RollbackError, Schema},
+    transactions::{
+        Accept, AcceptWithReceipt, ActivationHeights, CryptoTransactions, CustodialAcceptMany,
+        DelegatedAccept, EnabledTransactions, Error, RegisterAlias, Transfer,
+    },
+    verify_block, Config, DebugEvent, DebuggerOptions, EncryptedData, ExportedBlock,
+    FundsAttestation, ReplayError, SecretState, Service as Currency, TransactionCheck, CONFIG,
 };
 
 use std::{collections::HashSet, iter::FromIterator};
@@ -42,6 +53,12 @@ fn create_testkit() -> TestKit {
         .create()
 }
 
+fn create_testkit_with_config(config: Config) -> TestKit {
+    TestKitBuilder::validator()
+        .with_service(Currency::with_config(config))
+        .create()
+}
+
 #[test]
 fn create_2wallets_and_transfer_between_them() {
     let mut testkit = create_testkit();
@@ -290,6 +307,12 @@ where
     ];
     let expected_events: HashSet<&Event> = HashSet::from_iter(&expected_events);
     assert_eq!(HashSet::from_iter(&history[1..]), expected_events);
+    for transfer in &[&transfer_from_alice, &transfer_from_bob] {
+        let index = schema
+            .accepting_event(&transfer.hash())
+            .expect("accepting event");
+        assert_eq!(history[index as usize], Event::transfer(&transfer.hash()));
+    }
 
     carol_sec.transfer(&transfer_from_alice);
     carol_sec.transfer(&transfer_from_bob);
@@ -494,9 +517,53 @@ fn send_based_on_outdated_wallet_state_after_refund_works() {
     assert_eq!(bob_sec.balance(), INITIAL_BALANCE + 200);
 }
 
+#[test]
+fn available_funds_account_for_pending_outgoing_transfers() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let alice_pk = *alice_sec.public_key();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    const HIGH_THRESHOLD: u64 = INITIAL_BALANCE * 3 / 5;
+    const LOW_THRESHOLD: u64 = INITIAL_BALANCE * 2 / 5;
+    let stale_attestation = alice_sec
+        .attest_available_funds(HIGH_THRESHOLD)
+        .expect("attestation");
+
+    let transfer = alice_sec.create_transfer(INITIAL_BALANCE / 2, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    let schema = Schema::new(testkit.snapshot());
+    let alice_wallet = schema.wallet(&alice_pk).expect("Alice's wallet").info();
+    assert!(schema.unaccepted_transfers(&bob_pk).contains(&transfer.hash()));
+
+    // The pending outgoing transfer reduces available funds below the threshold.
+    assert!(!stale_attestation.verify(&alice_wallet));
+    alice_sec.transfer(&transfer);
+    assert!(alice_sec.attest_available_funds(HIGH_THRESHOLD).is_none());
+
+    // ...but not below the lower threshold.
+    let attestation = alice_sec
+        .attest_available_funds(LOW_THRESHOLD)
+        .expect("attestation");
+    // The attestation is passed to the verifier in the serialized form.
+    let bytes = attestation.to_bytes();
+    assert!(FundsAttestation::from_slice(&bytes[..bytes.len() - 1]).is_none());
+    let attestation = FundsAttestation::from_slice(&bytes).expect("from_slice");
+    assert_eq!(attestation.public_key, alice_pk);
+    assert_eq!(attestation.threshold, LOW_THRESHOLD);
+    assert!(attestation.verify(&alice_wallet));
+    let bob_wallet = schema.wallet(&bob_pk).expect("Bob's wallet").info();
+    assert!(!attestation.verify(&bob_wallet));
+}
+
 #[test]
 fn debugger() {
-    use private_currency::{DebugEvent, DebuggerOptions};
     use std::{
         sync::{Arc, RwLock},
         thread,
@@ -547,3 +614,1379 @@ fn debugger() {
     drop(testkit);
     handle.join().unwrap();
 }
+
+#[test]
+fn debugger_notifies_about_expiring_transfers() {
+    use std::{
+        sync::{Arc, RwLock},
+        thread,
+    };
+
+    const LEAD_TIME: u32 = 3;
+
+    let options = DebuggerOptions {
+        expiry_notice_blocks: LEAD_TIME,
+        ..DebuggerOptions::default()
+    };
+    let (currency, debugger) = Currency::debug(options);
+    let mut testkit = TestKitBuilder::validator().with_service(currency).create();
+
+    let debug_events = Arc::new(RwLock::new(vec![]));
+    let debug_events_ = debug_events.clone();
+    let handle = thread::spawn(move || {
+        for event in debugger {
+            debug_events_.write().expect("debug_events").push(event);
+        }
+    });
+
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let alice_pk = *alice_sec.public_key();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let alice_transfer = alice_sec.create_transfer(100, &bob_pk, 5);
+    let bob_transfer = bob_sec.create_transfer(200, &alice_pk, 7);
+    testkit.create_block_with_transactions(txvec![alice_transfer.clone(), bob_transfer.clone()]);
+
+    // Bob's transfer is accepted well before it expires, so there is no notification for it.
+    let verified = alice_sec
+        .verify_transfer(&bob_transfer)
+        .expect("verify_transfer");
+    let block = testkit.create_block_with_transaction(verified.accept);
+    assert!(block[0].status().is_ok());
+
+    testkit.create_blocks_until(Height(10));
+
+    // Alice's transfer is scheduled for rollback at height 7 and is rolled back in the block
+    // at height 8, so it can be accepted in blocks 6 to 8.
+    let debug_events = debug_events.read().expect("read debug_events").clone();
+    assert_eq!(
+        debug_events,
+        vec![
+            DebugEvent::ExpiresSoon {
+                transfer_id: alice_transfer.hash(),
+                receiver: bob_pk,
+                remaining_blocks: LEAD_TIME,
+                height: Height(5),
+            },
+            DebugEvent::RolledBack {
+                transfer: alice_transfer,
+                height: Height(8),
+            },
+        ]
+    );
+
+    drop(testkit);
+    handle.join().unwrap();
+}
+
+#[test]
+fn debugger_notifies_about_transfers_with_short_rollback_delay() {
+    use std::thread;
+
+    let options = DebuggerOptions {
+        expiry_notice_blocks: 10,
+        ..DebuggerOptions::default()
+    };
+    let (currency, debugger) = Currency::debug(options);
+    let mut testkit = TestKitBuilder::validator().with_service(currency).create();
+    let handle = thread::spawn(move || {
+        debugger
+            .filter(|event| match *event {
+                DebugEvent::ExpiresSoon { .. } => true,
+                _ => false,
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+
+    // The transfer can be accepted in blocks 3 to 8, which is less than the notice period.
+    let transfer = alice_sec.create_transfer(100, &bob_pk, 5);
+    testkit.create_block_with_transaction(transfer.clone());
+    testkit.create_blocks_until(Height(10));
+
+    drop(testkit);
+    let events = handle.join().unwrap();
+    assert_eq!(
+        events,
+        vec![DebugEvent::ExpiresSoon {
+            transfer_id: transfer.hash(),
+            receiver: bob_pk,
+            remaining_blocks: 6,
+            height: Height(2),
+        }]
+    );
+}
+
+#[test]
+fn two_phase_acceptance() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let alice_pk = *alice_sec.public_key();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    alice_sec.transfer(&transfer);
+
+    // The sender cannot commit the transfer before it is pre-accepted.
+    let block = testkit.create_block_with_transaction(alice_sec.create_commit(&transfer));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::NotPreAccepted as u8)
+    );
+
+    let verified = bob_sec.verify_transfer(&transfer).expect("verify_transfer");
+    let block = testkit.create_block_with_transaction(bob_sec.create_pre_accept(&transfer));
+    assert!(block[0].status().is_ok());
+
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.pre_accepted_transfers().contains(&transfer.hash()));
+    assert!(schema.unaccepted_transfers(&bob_pk).contains(&transfer.hash()));
+    let bob_wallet = schema.wallet(&bob_pk).expect("Bob's wallet");
+    assert_eq!(bob_wallet.info(), bob_sec.to_public());
+
+    // Once pre-accepted, the transfer cannot be accepted in a single step.
+    let block = testkit.create_block_with_transaction(verified.accept);
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::AlreadyPreAccepted as u8)
+    );
+
+    let block = testkit.create_block_with_transaction(alice_sec.create_commit(&transfer));
+    assert!(block[0].status().is_ok());
+    bob_sec.transfer(&transfer);
+
+    let schema = Schema::new(testkit.snapshot());
+    assert!(!schema.pre_accepted_transfers().contains(&transfer.hash()));
+    assert!(schema.unaccepted_transfers(&bob_pk).is_empty());
+    assert!(schema.rollback_transfers(Height(12)).is_empty());
+    let bob_history = schema.history(&bob_pk);
+    assert_eq!(bob_history.len(), 2);
+    assert_eq!(bob_history[1], Event::transfer(&transfer.hash()));
+
+    let alice_wallet = schema.wallet(&alice_pk).expect("Alice's wallet");
+    assert_eq!(alice_wallet.info(), alice_sec.to_public());
+    let bob_wallet = schema.wallet(&bob_pk).expect("Bob's wallet");
+    assert_eq!(bob_wallet.info(), bob_sec.to_public());
+    assert_eq!(bob_sec.balance(), INITIAL_BALANCE + 1_000);
+}
+
+#[test]
+fn pre_accepted_transfer_cannot_be_rejected() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    alice_sec.transfer(&transfer);
+    let block = testkit.create_block_with_transaction(bob_sec.create_pre_accept(&transfer));
+    assert!(block[0].status().is_ok());
+
+    let block = testkit.create_block_with_transaction(bob_sec.create_reject(&transfer));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::AlreadyPreAccepted as u8)
+    );
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.pre_accepted_transfers().contains(&transfer.hash()));
+    assert!(schema.unaccepted_transfers(&bob_pk).contains(&transfer.hash()));
+
+    // The sender can still commit the transfer.
+    let block = testkit.create_block_with_transaction(alice_sec.create_commit(&transfer));
+    assert!(block[0].status().is_ok());
+    let schema = Schema::new(testkit.snapshot());
+    assert_eq!(schema.history(&bob_pk).len(), 2);
+}
+
+#[test]
+fn pre_accepted_transfer_is_rolled_back_without_commit() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let alice_pk = *alice_sec.public_key();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 5);
+    testkit.create_block_with_transaction(transfer.clone());
+    alice_sec.transfer(&transfer);
+    let block = testkit.create_block_with_transaction(bob_sec.create_pre_accept(&transfer));
+    assert!(block[0].status().is_ok());
+
+    // The sender does not commit the transfer in time.
+    testkit.create_blocks_until(Height(10));
+
+    let schema = Schema::new(testkit.snapshot());
+    assert!(!schema.pre_accepted_transfers().contains(&transfer.hash()));
+    assert!(schema.unaccepted_transfers(&bob_pk).is_empty());
+    assert_eq!(schema.history(&bob_pk).len(), 1);
+    let alice_history = schema.history(&alice_pk);
+    assert_eq!(alice_history.len(), 3);
+    assert_eq!(alice_history[2], Event::rollback(&transfer.hash()));
+
+    alice_sec.rollback(&transfer);
+    let alice_wallet = schema.wallet(&alice_pk).expect("Alice's wallet");
+    assert_eq!(alice_wallet.info(), alice_sec.to_public());
+    assert_eq!(alice_sec.balance(), INITIAL_BALANCE);
+
+    // A late commit has no effect.
+    let block = testkit.create_block_with_transaction(alice_sec.create_commit(&transfer));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::NotPreAccepted as u8)
+    );
+}
+
+fn queue_at(height: Height, fork: &mut Fork) -> KeySetIndex<&mut Fork, Hash> {
+    KeySetIndex::new_in_family("private_currency.rollback_by_height", &height.0, fork)
+}
+
+#[test]
+fn rollback_queue_corruption_is_detected() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    let rollback_height = Height(12);
+    let schema = Schema::new(testkit.snapshot());
+    assert_eq!(schema.rollback_transfers(rollback_height), vec![transfer.hash()]);
+    assert_eq!(schema.verify_rollback_queue_integrity(&CONFIG), Ok(()));
+
+    // Dangling entry referencing a non-existing transfer.
+    let mut fork = testkit.blockchain().fork();
+    let unknown_id = crypto::hash(b"not a transfer");
+    queue_at(Height(20), &mut fork).insert(unknown_id);
+    assert_eq!(
+        Schema::new(&fork).verify_rollback_queue_integrity(&CONFIG),
+        Err(RollbackError::Dangling {
+            transfer_id: unknown_id,
+            height: Height(20),
+        })
+    );
+
+    // The transfer is scheduled twice.
+    let mut fork = testkit.blockchain().fork();
+    queue_at(Height(20), &mut fork).insert(transfer.hash());
+    assert_eq!(
+        Schema::new(&fork).verify_rollback_queue_integrity(&CONFIG),
+        Err(RollbackError::Duplicate {
+            transfer_id: transfer.hash(),
+            first: rollback_height,
+            second: Height(20),
+        })
+    );
+
+    // The transfer is scheduled at a wrong height.
+    let mut fork = testkit.blockchain().fork();
+    queue_at(rollback_height, &mut fork).remove(&transfer.hash());
+    queue_at(Height(20), &mut fork).insert(transfer.hash());
+    assert_eq!(
+        Schema::new(&fork).verify_rollback_queue_integrity(&CONFIG),
+        Err(RollbackError::Misplaced {
+            transfer_id: transfer.hash(),
+            expected: rollback_height,
+            actual: Height(20),
+        })
+    );
+
+    // The transfer is not scheduled at all.
+    let mut fork = testkit.blockchain().fork();
+    queue_at(rollback_height, &mut fork).remove(&transfer.hash());
+    assert_eq!(
+        Schema::new(&fork).verify_rollback_queue_integrity(&CONFIG),
+        Err(RollbackError::Missing {
+            transfer_id: transfer.hash(),
+        })
+    );
+}
+
+#[test]
+fn rollback_queue_integrity_respects_service_config() {
+    let config = Config {
+        rollback_delay_bounds: 5..2_000,
+        ..CONFIG
+    };
+    let mut testkit = create_testkit_with_config(config.clone());
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    // The delay exceeds the upper bound in the default configuration.
+    let transfer = alice_sec.create_transfer_with_config(1_000, &bob_pk, 1_500, &config);
+    let block = testkit.create_block_with_transaction(transfer.clone());
+    assert!(block[0].status().is_ok());
+    let rollback_height = Height(1_502);
+    let schema = Schema::new(testkit.snapshot());
+    assert_eq!(schema.rollback_transfers(rollback_height), vec![transfer.hash()]);
+    assert_eq!(schema.verify_rollback_queue_integrity(&config), Ok(()));
+    // The default configuration does not cover the height of the scheduled rollback.
+    assert_eq!(
+        schema.verify_rollback_queue_integrity(&CONFIG),
+        Err(RollbackError::Missing {
+            transfer_id: transfer.hash(),
+        })
+    );
+
+    // Entries below the current height are still checked.
+    testkit.create_blocks_until(Height(10));
+    let mut fork = testkit.blockchain().fork();
+    let unknown_id = crypto::hash(b"not a transfer");
+    queue_at(Height(5), &mut fork).insert(unknown_id);
+    assert_eq!(
+        Schema::new(&fork).verify_rollback_queue_integrity(&config),
+        Err(RollbackError::Dangling {
+            transfer_id: unknown_id,
+            height: Height(5),
+        })
+    );
+}
+
+#[test]
+fn disabled_transaction_types_are_rejected() {
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    let create_wallet = CryptoTransactions::CreateWallet(alice_sec.create_wallet());
+    alice_sec.initialize();
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    let transfer = CryptoTransactions::Transfer(transfer);
+
+    // All transaction types are enabled in the default configuration.
+    assert_eq!(CONFIG.enabled_transactions, EnabledTransactions::ALL);
+    assert!(CONFIG.enabled_transactions.check(&create_wallet).is_ok());
+    assert!(CONFIG.enabled_transactions.check(&transfer).is_ok());
+
+    let no_transfers = EnabledTransactions {
+        transfer: false,
+        ..EnabledTransactions::ALL
+    };
+    assert!(no_transfers.check(&create_wallet).is_ok());
+    let err = no_transfers.check(&transfer).unwrap_err();
+    assert_eq!(err.name(), "Transfer");
+    assert_eq!(
+        err.to_string(),
+        "`Transfer` transactions are disabled in this deployment"
+    );
+}
+
+#[test]
+fn disabled_transactions_fail_verification() {
+    let mut testkit = create_testkit_with_config(Config {
+        enabled_transactions: EnabledTransactions {
+            transfer: false,
+            ..EnabledTransactions::ALL
+        },
+        ..CONFIG
+    });
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    assert!(transfer.verify());
+    // The service parses transactions according to its configuration.
+    let transfer = testkit
+        .blockchain()
+        .tx_from_raw(transfer.raw().clone())
+        .unwrap();
+    assert!(!transfer.verify());
+
+    let block = testkit.create_block_with_transactions(vec![transfer]);
+    assert!(block.is_empty());
+    let schema = Schema::new(testkit.snapshot());
+    let alice_wallet = schema.wallet(alice_sec.public_key()).expect("Alice's wallet");
+    assert_eq!(alice_wallet.info(), alice_sec.to_public());
+}
+
+#[test]
+fn delegate_can_accept_transfers() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+    let (delegate_pk, delegate_sk) = crypto::gen_keypair();
+
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+        bob_sec.delegate_acceptance(&delegate_pk),
+    ]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.is_acceptance_delegate(&bob_pk, &delegate_pk));
+    assert!(!schema.is_acceptance_delegate(alice_sec.public_key(), &delegate_pk));
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+
+    let accept = DelegatedAccept::new(&bob_pk, &transfer.hash(), &delegate_pk, &delegate_sk);
+    let block = testkit.create_block_with_transaction(accept);
+    assert!(block[0].status().is_ok());
+    bob_sec.transfer(&transfer);
+
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.unaccepted_transfers(&bob_pk).is_empty());
+    let bob_wallet = schema.wallet(&bob_pk).expect("Bob's wallet");
+    assert_eq!(bob_wallet.info(), bob_sec.to_public());
+
+    // After the delegation is revoked, the delegate can no longer accept transfers.
+    testkit.create_block_with_transaction(bob_sec.revoke_acceptance_delegate(&delegate_pk));
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    let accept = DelegatedAccept::new(&bob_pk, &transfer.hash(), &delegate_pk, &delegate_sk);
+    let block = testkit.create_block_with_transaction(accept);
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::UnauthorizedAccept as u8)
+    );
+}
+
+#[test]
+fn delegate_cannot_transfer_funds() {
+    let mut testkit = create_testkit();
+    let (bob_pk, bob_sk) = crypto::gen_keypair();
+    let mut bob_sec = SecretState::from_keypair(bob_pk, bob_sk);
+    let mut carol_sec = SecretState::with_random_keypair();
+    let (delegate_pk, delegate_sk) = crypto::gen_keypair();
+
+    testkit.create_block_with_transactions(txvec![
+        bob_sec.create_wallet(),
+        carol_sec.create_wallet(),
+        bob_sec.delegate_acceptance(&delegate_pk),
+    ]);
+    bob_sec.initialize();
+    carol_sec.initialize();
+
+    // The delegate tries to spend Bob's funds by signing a transfer from Bob's wallet.
+    // Such a transfer has an invalid signature, so it is not included into the blockchain.
+    let mut forged_sec = SecretState::from_keypair(bob_pk, delegate_sk.clone());
+    forged_sec.initialize();
+    let forged_transfer = forged_sec.create_transfer(1_000, carol_sec.public_key(), 10);
+    let block = testkit.create_block_with_transaction(forged_transfer);
+    assert_eq!(block.len(), 0);
+
+    // A transfer from the delegate key itself fails, since the delegate is not a wallet.
+    let mut delegate_sec = SecretState::from_keypair(delegate_pk, delegate_sk);
+    delegate_sec.initialize();
+    let transfer = delegate_sec.create_transfer(1_000, carol_sec.public_key(), 10);
+    let block = testkit.create_block_with_transaction(transfer);
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::UnregisteredSender as u8)
+    );
+
+    let schema = Schema::new(testkit.snapshot());
+    let bob_wallet = schema.wallet(&bob_pk).expect("Bob's wallet");
+    assert_eq!(bob_wallet.info(), bob_sec.to_public());
+    assert!(schema.unaccepted_transfers(carol_sec.public_key()).is_empty());
+}
+
+#[test]
+fn delegate_key_cannot_be_squatted() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    let mallory_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+    let (delegate_pk, delegate_sk) = crypto::gen_keypair();
+
+    // Mallory registers Bob's intended delegate key for her own wallet first.
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+        mallory_sec.create_wallet(),
+        mallory_sec.delegate_acceptance(&delegate_pk),
+    ]);
+    alice_sec.initialize();
+
+    // This does not prevent Bob from authorizing the same key.
+    let block = testkit.create_block_with_transaction(bob_sec.delegate_acceptance(&delegate_pk));
+    assert!(block[0].status().is_ok());
+    let block = testkit.create_block_with_transaction(bob_sec.delegate_acceptance(&delegate_pk));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::DelegateExists as u8)
+    );
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    let accept = DelegatedAccept::new(&bob_pk, &transfer.hash(), &delegate_pk, &delegate_sk);
+    let block = testkit.create_block_with_transaction(accept);
+    assert!(block[0].status().is_ok());
+
+    // Mallory revoking her own authorization does not affect Bob's one.
+    testkit.create_block_with_transaction(mallory_sec.revoke_acceptance_delegate(&delegate_pk));
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.is_acceptance_delegate(&bob_pk, &delegate_pk));
+    assert!(!schema.is_acceptance_delegate(mallory_sec.public_key(), &delegate_pk));
+}
+
+#[test]
+fn unverifiable_transfer_can_be_rejected() {
+    let mut testkit = create_testkit();
+    let (alice_pk, alice_sk) = crypto::gen_keypair();
+    let mut alice_sec = SecretState::from_keypair(alice_pk, alice_sk.clone());
+    let mut bob_sec = SecretState::with_random_keypair();
+    let mut carol_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+        carol_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+    carol_sec.initialize();
+
+    // Alice addresses a transfer to Bob, but encrypts its opening for Carol.
+    let to_carol = alice_sec.create_transfer(1_000, carol_sec.public_key(), 10);
+    let transfer = Transfer::new(
+        &alice_pk,
+        &bob_pk,
+        10,
+        to_carol.history_len(),
+        to_carol.amount(),
+        to_carol.amount_proof(),
+        to_carol.sufficient_balance_proof(),
+        to_carol.encrypted_data(),
+        &alice_sk,
+    );
+    let block = testkit.create_block_with_transaction(transfer.clone());
+    assert!(block[0].status().is_ok());
+    // Alice's secrets are updated via `to_carol`, since it has the same amount.
+    alice_sec.transfer(&to_carol);
+
+    let reject = match check_incoming_transfer(&bob_sec, &transfer) {
+        Some(IncomingTransfer::Unverifiable(reject)) => reject,
+        other => panic!("unexpected check result: {:?}", other),
+    };
+    assert!(check_incoming_transfer(&carol_sec, &transfer).is_none());
+    let block = testkit.create_block_with_transaction(reject);
+    assert!(block[0].status().is_ok());
+
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.rollback_transfers(Height(12)).is_empty());
+    assert!(schema.unaccepted_transfers(&bob_pk).is_empty());
+    assert_eq!(schema.history(&bob_pk).len(), 1);
+    let alice_history = schema.history(&alice_pk);
+    assert_eq!(alice_history.len(), 3);
+    assert_eq!(alice_history[2], Event::rollback(&transfer.hash()));
+    let status: TransferStatus = testkit
+        .api()
+        .public(ApiKind::Service("private_currency"))
+        .query(&TransferQuery {
+            id: transfer.hash(),
+        })
+        .get("v1/transfer")
+        .expect("transfer status");
+    assert_eq!(status.stage, TransferStage::Rejected);
+    assert!(status.stage.is_settled());
+
+    alice_sec.rollback(&to_carol);
+    let alice_wallet = schema.wallet(&alice_pk).expect("Alice's wallet");
+    assert_eq!(alice_wallet.info(), alice_sec.to_public());
+    assert_eq!(alice_sec.balance(), INITIAL_BALANCE);
+
+    // The rejected transfer cannot be accepted or rejected again.
+    let block = testkit.create_block_with_transaction(bob_sec.create_reject(&transfer));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::UnknownTransfer as u8)
+    );
+}
+
+#[test]
+fn transfer_to_unregistered_key_fails_by_default() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transaction(alice_sec.create_wallet());
+    alice_sec.initialize();
+
+    assert!(!CONFIG.auto_create_receivers);
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    let block = testkit.create_block_with_transaction(transfer);
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::UnregisteredReceiver as u8)
+    );
+
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.wallet(bob_sec.public_key()).is_none());
+    let alice_wallet = schema.wallet(alice_sec.public_key()).expect("Alice's wallet");
+    assert_eq!(alice_wallet.info(), alice_sec.to_public());
+}
+
+#[test]
+fn transfer_can_create_receiver_wallet() {
+    let mut testkit = create_testkit_with_config(Config {
+        auto_create_receivers: true,
+        ..CONFIG
+    });
+    let (alice_pk, alice_sk) = crypto::gen_keypair();
+    let mut alice_sec = SecretState::from_keypair(alice_pk, alice_sk.clone());
+    let mut bob_sec = SecretState::with_random_keypair();
+    let bob_pk = *bob_sec.public_key();
+    testkit.create_block_with_transaction(alice_sec.create_wallet());
+    alice_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    let block = testkit.create_block_with_transaction(transfer.clone());
+    assert!(block[0].status().is_ok());
+    alice_sec.transfer(&transfer);
+    bob_sec.initialize();
+
+    {
+        let schema = Schema::new(testkit.snapshot());
+        let bob_wallet = schema.wallet(&bob_pk).expect("Bob's wallet");
+        assert_eq!(bob_wallet.info(), bob_sec.to_public());
+        assert_eq!(
+            schema.history(&bob_pk),
+            vec![Event::implicit_create_wallet(&transfer.hash())]
+        );
+        assert_eq!(
+            schema.unaccepted_transfers(&bob_pk),
+            HashSet::from_iter(vec![transfer.hash()])
+        );
+        let alice_wallet = schema.wallet(&alice_pk).expect("Alice's wallet");
+        assert_eq!(alice_wallet.info(), alice_sec.to_public());
+    }
+    // The encryption key of the created wallet is derived from its key as usual.
+    let verified = bob_sec.verify_transfer(&transfer).expect("verify transfer");
+    assert_eq!(verified.value(), 1_000);
+
+    // Keys that are not Ed25519 points cannot be converted into encryption keys,
+    // so wallets are not created for them. The same applies to small-order points,
+    // such as the identity point.
+    let invalid_key = (0..=u8::max_value())
+        .map(|i| [i; 32])
+        .find(|bytes| CompressedEdwardsY(*bytes).decompress().is_none())
+        .map(|bytes| PublicKey::from_slice(&bytes).unwrap())
+        .unwrap();
+    let mut identity = [0; 32];
+    identity[0] = 1;
+    let small_order_key = PublicKey::from_slice(&identity).unwrap();
+
+    for key in &[invalid_key, small_order_key] {
+        let transfer = Transfer::new(
+            &alice_pk,
+            key,
+            10,
+            transfer.history_len(),
+            transfer.amount(),
+            transfer.amount_proof(),
+            transfer.sufficient_balance_proof(),
+            transfer.encrypted_data(),
+            &alice_sk,
+        );
+        let block = testkit.create_block_with_transaction(transfer);
+        assert_eq!(
+            block[0].status().unwrap_err().error_type(),
+            TransactionErrorType::Code(Error::UnregisteredReceiver as u8)
+        );
+        assert!(Schema::new(testkit.snapshot()).wallet(key).is_none());
+    }
+}
+
+/// Creates a chain with transfers, an acceptance and a rollback, and returns the blocks
+/// exported from it, together with the testkit rolled back to the genesis block.
+/// Since the state hash in block headers depends on the genesis configuration,
+/// the blocks can only be replayed on the returned testkit.
+fn export_scripted_chain() -> (TestKit, Vec<ExportedBlock>) {
+    use std::thread;
+
+    let options = DebuggerOptions {
+        export_blocks: true,
+        ..DebuggerOptions::default()
+    };
+    let (currency, debugger) = Currency::debug(options);
+    let mut testkit = TestKitBuilder::validator().with_service(currency).create();
+    testkit.checkpoint();
+    let handle = thread::spawn(move || {
+        debugger
+            .filter_map(|event| match event {
+                DebugEvent::BlockCommitted(block) => Some(block),
+                _ => None,
+            })
+            .take(12)
+            .collect::<Vec<_>>()
+    });
+
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    let alice_pk = *alice_sec.public_key();
+    let bob_pk = *bob_sec.public_key();
+
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let accepted_transfer = alice_sec.create_transfer(100, &bob_pk, 5);
+    let expired_transfer = bob_sec.create_transfer(200, &alice_pk, 7);
+    testkit.create_block_with_transactions(txvec![
+        accepted_transfer.clone(),
+        expired_transfer.clone(),
+    ]);
+    let accept = bob_sec
+        .verify_transfer(&accepted_transfer)
+        .expect("verify transfer")
+        .accept;
+    // The commit fails since the transfer is not pre-accepted; failed transactions
+    // are exported as well.
+    let commit = alice_sec.create_commit(&accepted_transfer);
+    testkit.create_block_with_transactions(txvec![accept, commit]);
+    testkit.create_blocks_until(Height(12)); // let the second transfer expire
+
+    let expected_state_hash = Schema::new(testkit.snapshot()).state_hash();
+    let exported: Vec<ExportedBlock> = handle.join().unwrap();
+    assert_eq!(exported.len(), 12);
+    assert_eq!(exported[1].transactions.len(), 2);
+    assert_eq!(exported[2].transactions.len(), 2);
+    assert_eq!(exported[11].state_hash, Some(expected_state_hash));
+
+    // Blocks can be exported after the fact as well, albeit without the service state hash.
+    let snapshot = testkit.snapshot();
+    for block in &exported[..11] {
+        let mut expected = block.clone();
+        expected.state_hash = None;
+        assert_eq!(
+            ExportedBlock::at_height(snapshot.as_ref(), block.height()),
+            Some(expected)
+        );
+    }
+    assert_eq!(
+        ExportedBlock::at_height(snapshot.as_ref(), Height(12)),
+        Some(exported[11].clone())
+    );
+    assert!(ExportedBlock::at_height(snapshot.as_ref(), Height(13)).is_none());
+
+    testkit.rollback();
+    (testkit, exported)
+}
+
+#[test]
+fn exported_blocks_can_be_replayed() {
+    let (mut testkit, exported) = export_scripted_chain();
+
+    // Replay the blocks on a blockchain with the same genesis block.
+    testkit.checkpoint();
+    replay_blocks(testkit.blockchain_mut(), &exported).unwrap();
+    assert_eq!(testkit.height(), Height(12));
+    let schema = Schema::new(testkit.snapshot());
+    assert_eq!(Some(schema.state_hash()), exported[11].state_hash);
+    testkit.rollback();
+
+    // Blocks exported without the service state hash are checked against block headers.
+    let without_state_hash: Vec<_> = exported
+        .iter()
+        .cloned()
+        .map(|mut block| {
+            block.state_hash = None;
+            block
+        })
+        .collect();
+    testkit.checkpoint();
+    replay_blocks(testkit.blockchain_mut(), &without_state_hash).unwrap();
+    assert_eq!(testkit.height(), Height(12));
+    testkit.rollback();
+
+    // A blockchain with another genesis configuration leads to different state hashes.
+    let mut other_testkit = create_testkit();
+    assert_eq!(
+        replay_blocks(other_testkit.blockchain_mut(), &exported),
+        Err(ReplayError::StateHashMismatch { height: Height(1) })
+    );
+
+    // Blocks must be replayed starting from the next height.
+    assert_eq!(
+        replay_blocks(testkit.blockchain_mut(), &exported[1..]),
+        Err(ReplayError::HeightMismatch {
+            expected: Height(1),
+            actual: Height(2),
+        })
+    );
+
+    // Replaying stops at a block with an unexpected state hash.
+    let mut tampered = exported.clone();
+    let header = &exported[2].header;
+    tampered[2].header = Block::new(
+        header.proposer_id(),
+        header.height(),
+        header.tx_count(),
+        header.prev_hash(),
+        header.tx_hash(),
+        exported[1].header.state_hash(),
+    );
+    assert_eq!(
+        replay_blocks(testkit.blockchain_mut(), &tampered),
+        Err(ReplayError::StateHashMismatch { height: Height(3) })
+    );
+    assert_eq!(testkit.height(), Height(3));
+}
+
+#[test]
+fn exported_blocks_can_be_verified() {
+    let (mut testkit, exported) = export_scripted_chain();
+
+    for block in &exported {
+        let report = verify_block(testkit.blockchain().fork(), block, &CONFIG).unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.height, block.height());
+        assert_eq!(report.state_hash_matches, Some(true));
+        assert_eq!(block.state_hash.as_ref(), Some(&report.state_hash));
+        assert_eq!(report.transactions.len(), block.transactions.len());
+        if block.height() == Height(3) {
+            // The commit in the third block fails.
+            assert_eq!(report.transactions[0].1, TransactionCheck::Executed);
+            match report.transactions[1].1 {
+                TransactionCheck::Failed(ref e) => assert_eq!(
+                    e.error_type(),
+                    TransactionErrorType::Code(Error::NotPreAccepted as u8)
+                ),
+                ref check => panic!("unexpected check: {:?}", check),
+            }
+        } else {
+            assert!(
+                report
+                    .transactions
+                    .iter()
+                    .all(|(_, check)| *check == TransactionCheck::Executed)
+            );
+        }
+        // Verification does not affect the blockchain.
+        assert_eq!(testkit.height().next(), block.height());
+        assert_eq!(
+            CoreSchema::new(testkit.snapshot()).transactions_pool_len(),
+            0
+        );
+        replay_blocks(testkit.blockchain_mut(), Some(block)).unwrap();
+    }
+
+    // Blocks can only be verified on top of the pre-block state.
+    assert_eq!(
+        verify_block(testkit.blockchain().fork(), &exported[0], &CONFIG).unwrap_err(),
+        ReplayError::HeightMismatch {
+            expected: Height(13),
+            actual: Height(1),
+        }
+    );
+}
+
+#[test]
+fn block_verification_resolves_transfers_from_the_same_block() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    let accept = bob_sec
+        .verify_transfer(&transfer)
+        .expect("verify transfer")
+        .accept;
+    testkit.checkpoint();
+    let block = testkit.create_block_with_transactions(txvec![transfer, accept]);
+    assert!(block.iter().all(|tx| tx.status().is_ok()));
+    let block = ExportedBlock::latest(testkit.snapshot().as_ref());
+    testkit.rollback();
+
+    let report = verify_block(testkit.blockchain().fork(), &block, &CONFIG).unwrap();
+    assert!(report.is_ok(), "{:?}", report);
+    assert!(report
+        .transactions
+        .iter()
+        .all(|(_, check)| *check == TransactionCheck::Executed));
+}
+
+#[test]
+fn block_verification_uses_supplied_config() {
+    let config = Config {
+        rollback_delay_bounds: 5..2_000,
+        ..CONFIG
+    };
+    let mut testkit = create_testkit_with_config(config.clone());
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+
+    let transfer =
+        alice_sec.create_transfer_with_config(1_000, bob_sec.public_key(), 1_500, &config);
+    testkit.checkpoint();
+    let block = testkit.create_block_with_transaction(transfer.clone());
+    assert!(block[0].status().is_ok());
+    let block = ExportedBlock::latest(testkit.snapshot().as_ref());
+    testkit.rollback();
+
+    let report = verify_block(testkit.blockchain().fork(), &block, &config).unwrap();
+    assert!(report.is_ok(), "{:?}", report);
+    assert_eq!(
+        report.transactions,
+        vec![(transfer.hash(), TransactionCheck::Executed)]
+    );
+
+    // The rollback delay of the transfer is out of bounds for the default config.
+    let report = verify_block(testkit.blockchain().fork(), &block, &CONFIG).unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(
+        report.transactions,
+        vec![(transfer.hash(), TransactionCheck::Invalid)]
+    );
+}
+
+#[test]
+fn tampered_block_fails_verification() {
+    let (mut testkit, exported) = export_scripted_chain();
+    replay_blocks(testkit.blockchain_mut(), &exported[..1]).unwrap();
+
+    // Replace the first transfer in the second block with a copy signed by another key.
+    let mut block = exported[1].clone();
+    let tampered = match block.transactions[0] {
+        CryptoTransactions::Transfer(ref transfer) => Transfer::new(
+            transfer.from(),
+            transfer.to(),
+            transfer.rollback_delay(),
+            transfer.history_len(),
+            transfer.amount(),
+            transfer.amount_proof(),
+            transfer.sufficient_balance_proof(),
+            transfer.encrypted_data(),
+            &crypto::gen_keypair().1,
+        ),
+        ref tx => panic!("unexpected transaction: {:?}", tx),
+    };
+    let tampered_hash = tampered.hash();
+    block.transactions[0] = CryptoTransactions::Transfer(tampered);
+
+    let report = verify_block(testkit.blockchain().fork(), &block, &CONFIG).unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(
+        report.transactions[0],
+        (tampered_hash, TransactionCheck::Invalid)
+    );
+    assert_eq!(report.transactions[1].1, TransactionCheck::Executed);
+    assert_eq!(report.state_hash_matches, Some(false));
+    assert_ne!(Some(report.state_hash), block.state_hash);
+}
+
+#[test]
+fn aliases_are_unique() {
+    let mut testkit = create_testkit();
+    let alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+
+    let block = testkit.create_block_with_transaction(alice_sec.register_alias("alice"));
+    assert!(block[0].status().is_ok());
+    let schema = Schema::new(testkit.snapshot());
+    assert_eq!(schema.resolve_alias("alice"), Some(*alice_sec.public_key()));
+    assert_eq!(
+        schema.wallet_alias(alice_sec.public_key()),
+        Some("alice".to_owned())
+    );
+    assert_eq!(schema.resolve_alias("bob"), None);
+
+    // The alias cannot be registered by another wallet.
+    let block = testkit.create_block_with_transaction(bob_sec.register_alias("alice"));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::AliasTaken as u8)
+    );
+    // A wallet cannot register a second alias.
+    let block = testkit.create_block_with_transaction(alice_sec.register_alias("alice-2"));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::AliasAlreadyRegistered as u8)
+    );
+    // Only registered wallets may have aliases.
+    let carol_sec = SecretState::with_random_keypair();
+    let block = testkit.create_block_with_transaction(carol_sec.register_alias("carol"));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::UnregisteredOwner as u8)
+    );
+
+    // Invalid aliases are not included into the blockchain.
+    let (pk, sk) = crypto::gen_keypair();
+    let long_alias = "b".repeat(33);
+    let invalid_aliases = ["", "Bob", "bob smith", "b\u{f6}b", long_alias.as_str()];
+    for alias in &invalid_aliases {
+        let tx = RegisterAlias::new(&pk, alias, &sk);
+        let block = testkit.create_block_with_transaction(tx);
+        assert_eq!(block.len(), 0);
+    }
+
+    let schema = Schema::new(testkit.snapshot());
+    assert_eq!(schema.resolve_alias("alice"), Some(*alice_sec.public_key()));
+    assert_eq!(schema.wallet_alias(bob_sec.public_key()), None);
+    assert_eq!(schema.resolve_alias("carol"), None);
+}
+
+#[test]
+fn accepted_balance_delta_equals_transfer_amount() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    let other_transfer = alice_sec.create_transfer(999, bob_sec.public_key(), 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    let accept = bob_sec.verify_transfer(&transfer).unwrap().accept;
+    let block = testkit.create_block_with_transaction(accept);
+    assert!(block[0].status().is_ok());
+
+    let schema = Schema::new(testkit.snapshot());
+    let bob = schema.wallet(bob_sec.public_key()).unwrap();
+    assert_eq!(bob.history_len(), 2);
+    let balance_before = schema.past_balance(bob_sec.public_key(), 0).unwrap();
+    let balance_after = schema.past_balance(bob_sec.public_key(), 1).unwrap();
+    assert_eq!(balance_after, bob.balance());
+
+    assert!(is_accepted_balance(&balance_before, &balance_after, &transfer));
+    assert!(!is_accepted_balance(&balance_before, &balance_after, &other_transfer));
+    assert!(!is_accepted_balance(&balance_before, &balance_before, &transfer));
+
+    // Past balances for the acceptance are removed once Bob sends a transfer.
+    bob_sec.transfer(&transfer);
+    let outgoing = bob_sec.create_transfer(100, alice_sec.public_key(), 10);
+    let block = testkit.create_block_with_transaction(outgoing);
+    assert!(block[0].status().is_ok());
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.past_balance(bob_sec.public_key(), 0).is_none());
+    assert!(schema.past_balance(bob_sec.public_key(), 1).is_none());
+}
+
+#[test]
+fn custodian_can_accept_transfers_into_several_wallets() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let (custodian_pk, custodian_sk) = crypto::gen_keypair();
+    let mut wallets: Vec<_> = (0..3)
+        .map(|_| (SecretState::with_random_keypair(), crypto::gen_keypair()))
+        .collect();
+
+    let mut txs: Vec<Box<dyn Transaction>> = vec![Box::new(alice_sec.create_wallet())];
+    for (wallet, (delegate_pk, _)) in &wallets {
+        txs.push(Box::new(wallet.create_wallet()));
+        txs.push(Box::new(wallet.delegate_acceptance(delegate_pk)));
+    }
+    let block = testkit.create_block_with_transactions(txs);
+    assert!(block.iter().all(|tx| tx.status().is_ok()));
+    alice_sec.initialize();
+    for (wallet, _) in &mut wallets {
+        wallet.initialize();
+    }
+
+    let mut transfers = vec![];
+    for (wallet, _) in &wallets {
+        let transfer = alice_sec.create_transfer(1_000, wallet.public_key(), 10);
+        let block = testkit.create_block_with_transaction(transfer.clone());
+        assert!(block[0].status().is_ok());
+        alice_sec.transfer(&transfer);
+        transfers.push(transfer);
+    }
+
+    let accepts: Vec<_> = wallets
+        .iter()
+        .zip(&transfers)
+        .map(|((wallet, (delegate_pk, delegate_sk)), transfer)| {
+            let accept = DelegatedAccept::new(
+                wallet.public_key(),
+                &transfer.hash(),
+                delegate_pk,
+                delegate_sk,
+            );
+            accept.raw().clone()
+        })
+        .collect();
+
+    // If one of the accepts fails, no transfers are accepted.
+    let mut invalid_accepts = accepts.clone();
+    invalid_accepts.push(accepts[0].clone());
+    let batch = CustodialAcceptMany::new(&custodian_pk, invalid_accepts, &custodian_sk);
+    let block = testkit.create_block_with_transaction(batch);
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::UnknownTransfer as u8)
+    );
+    let schema = Schema::new(testkit.snapshot());
+    for ((wallet, _), transfer) in wallets.iter().zip(&transfers) {
+        assert!(schema
+            .unaccepted_transfers(wallet.public_key())
+            .contains(&transfer.hash()));
+    }
+
+    let batch = CustodialAcceptMany::new(&custodian_pk, accepts, &custodian_sk);
+    let block = testkit.create_block_with_transaction(batch);
+    assert!(block[0].status().is_ok());
+
+    let schema = Schema::new(testkit.snapshot());
+    for ((wallet, _), transfer) in wallets.iter_mut().zip(&transfers) {
+        wallet.transfer(transfer);
+        assert!(schema.unaccepted_transfers(wallet.public_key()).is_empty());
+        let info = schema.wallet(wallet.public_key()).unwrap().info();
+        assert_eq!(wallet.balance(), INITIAL_BALANCE + 1_000);
+        assert!(wallet.corresponds_to(&info));
+    }
+    let alice = schema.wallet(alice_sec.public_key()).unwrap().info();
+    assert!(alice_sec.corresponds_to(&alice));
+}
+
+#[test]
+fn custodial_accept_requires_authorized_signers() {
+    let mut testkit = create_testkit();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    let (custodian_pk, custodian_sk) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    testkit.create_block_with_transaction(transfer.clone());
+
+    // The custodian is not a delegate of Bob.
+    let accept = DelegatedAccept::new(
+        bob_sec.public_key(),
+        &transfer.hash(),
+        &custodian_pk,
+        &custodian_sk,
+    );
+    let batch = CustodialAcceptMany::new(&custodian_pk, vec![accept.raw().clone()], &custodian_sk);
+    let block = testkit.create_block_with_transaction(batch);
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::UnauthorizedAccept as u8)
+    );
+
+    // Empty batches and batches with non-`Accept` transactions are invalid.
+    let empty = CustodialAcceptMany::new(&custodian_pk, vec![], &custodian_sk);
+    assert!(!empty.verify());
+    let with_transfer =
+        CustodialAcceptMany::new(&custodian_pk, vec![transfer.raw().clone()], &custodian_sk);
+    assert!(!with_transfer.verify());
+}
+
+/// Registers wallets for Alice and Bob and commits a transfer between them. Returns
+/// two next transfers: one copying the encrypted data of the committed transfer
+/// and a regular one.
+fn create_duplicate_transfers(testkit: &mut TestKit) -> (Transfer, Transfer) {
+    let (alice_pk, alice_sk) = crypto::gen_keypair();
+    let mut alice_sec = SecretState::from_keypair(alice_pk, alice_sk.clone());
+    let bob_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transactions(txvec![
+        alice_sec.create_wallet(),
+        bob_sec.create_wallet(),
+    ]);
+    alice_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    alice_sec.transfer(&transfer);
+
+    let next_transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    let copied_transfer = Transfer::new(
+        &alice_pk,
+        bob_sec.public_key(),
+        10,
+        next_transfer.history_len(),
+        next_transfer.amount(),
+        next_transfer.amount_proof(),
+        next_transfer.sufficient_balance_proof(),
+        transfer.encrypted_data(),
+        &alice_sk,
+    );
+    (copied_transfer, next_transfer)
+}
+
+#[test]
+fn transfers_with_duplicate_encrypted_data_are_rejected_under_policy() {
+    let mut testkit = create_testkit_with_config(Config {
+        encrypted_data_dedup_window: 10,
+        ..CONFIG
+    });
+    let (copied_transfer, next_transfer) = create_duplicate_transfers(&mut testkit);
+    let block = testkit.create_block_with_transaction(copied_transfer);
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::DuplicateEncryptedData as u8)
+    );
+    let block = testkit.create_block_with_transaction(next_transfer);
+    assert!(block[0].status().is_ok());
+
+    // The check is disabled by default.
+    let mut testkit = create_testkit();
+    let (copied_transfer, _) = create_duplicate_transfers(&mut testkit);
+    let block = testkit.create_block_with_transaction(copied_transfer);
+    assert!(block[0].status().is_ok());
+}
+
+#[test]
+fn transaction_types_are_active_from_activation_height() {
+    let heights = ActivationHeights {
+        register_alias: 5,
+        ..ActivationHeights::GENESIS
+    };
+    let mut alice_sec = SecretState::with_random_keypair();
+    let bob_sec = SecretState::with_random_keypair();
+    alice_sec.initialize();
+    let register_alias = CryptoTransactions::RegisterAlias(alice_sec.register_alias("alice"));
+    let transfer = alice_sec.create_transfer(1_000, bob_sec.public_key(), 10);
+    let reject = CryptoTransactions::Reject(bob_sec.create_reject(&transfer));
+    let transfer = CryptoTransactions::Transfer(transfer);
+
+    assert_eq!(heights.activation_height(&register_alias), Height(5));
+    assert!(!heights.is_active(&register_alias, Height(1)));
+    assert!(!heights.is_active(&register_alias, Height(4)));
+    assert!(heights.is_active(&register_alias, Height(5)));
+    assert!(heights.is_active(&register_alias, Height(6)));
+    // Other transaction types are unaffected.
+    assert!(heights.is_active(&reject, Height(1)));
+    assert!(heights.is_active(&transfer, Height(1)));
+
+    // All transaction types are active with the default config.
+    assert_eq!(CONFIG.activation_heights, ActivationHeights::GENESIS);
+    let mut testkit = create_testkit();
+    testkit.create_block_with_transaction(alice_sec.create_wallet());
+    let block = testkit.create_block_with_transaction(alice_sec.register_alias("alice"));
+    assert!(block[0].status().is_ok());
+}
+
+#[test]
+fn inactive_transactions_fail_before_activation_height() {
+    let mut testkit = create_testkit_with_config(Config {
+        activation_heights: ActivationHeights {
+            register_alias: 5,
+            ..ActivationHeights::GENESIS
+        },
+        ..CONFIG
+    });
+    let alice_sec = SecretState::with_random_keypair();
+    testkit.create_block_with_transaction(alice_sec.create_wallet());
+
+    let block = testkit.create_block_with_transaction(alice_sec.register_alias("alice"));
+    assert_eq!(block.height(), Height(2));
+    assert_eq!(
+        block[0].status().unwrap_err().error_type(),
+        TransactionErrorType::Code(Error::InactiveTransaction as u8)
+    );
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.resolve_alias("alice").is_none());
+
+    testkit.create_blocks_until(Height(4));
+    let block = testkit.create_block_with_transaction(alice_sec.register_alias("alice-5"));
+    assert_eq!(block.height(), Height(5));
+    assert!(block[0].status().is_ok());
+}
+
+#[test]
+fn transfers_can_be_accepted_with_receipt() {
+    const RECEIPT: &[u8] = b"invoice #42";
+
+    let mut testkit = create_testkit();
+    let (bob_pk, bob_sk) = crypto::gen_keypair();
+    let mut alice_sec = SecretState::with_random_keypair();
+    let mut bob_sec = SecretState::from_keypair(bob_pk, bob_sk.clone());
+    testkit
+        .create_block_with_transactions(txvec![alice_sec.create_wallet(), bob_sec.create_wallet()]);
+    alice_sec.initialize();
+    bob_sec.initialize();
+
+    let transfer = alice_sec.create_transfer(1_000, &bob_pk, 10);
+    testkit.create_block_with_transaction(transfer.clone());
+    alice_sec.transfer(&transfer);
+
+    bob_sec.verify_transfer(&transfer).expect("verify_transfer");
+    let accept = bob_sec.create_accept_with_receipt(&transfer, RECEIPT);
+    let block = testkit.create_block_with_transaction(accept);
+    assert!(block[0].status().is_ok());
+    bob_sec.transfer(&transfer);
+
+    let schema = Schema::new(testkit.snapshot());
+    assert!(schema.unaccepted_transfers(&bob_pk).is_empty());
+    let bob_wallet = schema.wallet(&bob_pk).expect("Bob's wallet").info();
+    assert!(bob_sec.corresponds_to(&bob_wallet));
+
+    // The sender retrieves the receipt and decrypts it.
+    let receipt = schema.receipt(&transfer.hash()).expect("receipt");
+    assert_eq!(
+        alice_sec.open_receipt(&transfer, &receipt),
+        Some(RECEIPT.to_vec())
+    );
+
+    // A modified receipt fails authentication.
+    let mut ciphertext = receipt.encrypted_data().to_vec();
+    ciphertext[0] ^= 1;
+    let forged_receipt = EncryptedData::new(receipt.nonce(), &ciphertext);
+    assert_eq!(alice_sec.open_receipt(&transfer, &forged_receipt), None);
+
+    // Receipts exceeding the configured length do not pass verification.
+    let long_ciphertext = vec![0; CONFIG.max_receipt_len as usize + ciphertext.len()];
+    let long_receipt = EncryptedData::new(receipt.nonce(), &long_ciphertext);
+    let accept = AcceptWithReceipt::new(&bob_pk, &transfer.hash(), long_receipt, &bob_sk);
+    assert!(!accept.verify());
+}